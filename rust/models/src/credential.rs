@@ -13,6 +13,14 @@ pub struct Credential {
     pub service: Service,
     /// Port used for getting access. If missing a standard port is used
     pub port: Option<u16>,
+    #[cfg_attr(
+        feature = "serde_support",
+        serde(skip_serializing_if = "Option::is_none", default)
+    )]
+    /// Reference to a secret held by an external credential provider instead
+    /// of embedding it directly. When set, the secret fields of
+    /// `credential_type` are empty placeholders until resolved.
+    pub reference: Option<CredentialReference>,
     #[cfg_attr(feature = "serde_support", serde(flatten))]
     /// Type of the credential to get access. Different services support different types.
     pub credential_type: CredentialType,
@@ -27,18 +35,28 @@ impl Credential {
         Ok(Credential {
             service: self.service,
             port: self.port,
+            reference: self.reference,
             credential_type: self.credential_type.map_password(f)?,
         })
     }
 
-    /// Gets the password of the credential.
+    /// Gets the password of the credential. Agent-backed credential types
+    /// have no password, since signing is delegated to the agent; an empty
+    /// string is returned for those.
     pub fn password(&self) -> &str {
         match &self.credential_type {
             CredentialType::UP { password, .. } => password,
             CredentialType::USK { password, .. } => password,
             CredentialType::SNMP { password, .. } => password,
+            CredentialType::USKAgent { .. } => "",
         }
     }
+
+    /// Whether the secret of this credential must be resolved through a
+    /// `CredentialResolver` before the credential can be used.
+    pub fn is_reference(&self) -> bool {
+        self.reference.is_some()
+    }
 }
 
 impl Default for Credential {
@@ -46,6 +64,7 @@ impl Default for Credential {
         Self {
             service: Service::SSH,
             port: Default::default(),
+            reference: Default::default(),
             credential_type: CredentialType::UP {
                 username: "root".to_string(),
                 password: "".to_string(),
@@ -54,6 +73,24 @@ impl Default for Credential {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+/// Points a [`Credential`] at a secret held by an external credential
+/// provider rather than embedding it in the scan payload.
+pub struct CredentialReference {
+    /// Name of the configured provider that should resolve this reference,
+    /// e.g. the name a process-based provider was registered under.
+    pub provider: String,
+    /// Opaque lookup key passed to the provider, e.g. a vault path or secret
+    /// name. Never a secret itself, but censored defensively since it may
+    /// reveal naming conventions about the target infrastructure.
+    #[cfg_attr(feature = "serde_support", serde(serialize_with = "crate::censor"))]
+    pub key: String,
+}
+
 /// Enum of available services
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(
@@ -117,6 +154,22 @@ pub enum CredentialType {
         #[cfg_attr(feature = "serde_support", serde(serialize_with = "crate::censor"))]
         private_key: String,
     },
+    #[cfg_attr(feature = "serde_support", serde(rename = "usk_agent"))]
+    /// User/ssh-key credentials whose private key is never embedded;
+    /// signing is instead delegated to an ssh-agent.
+    USKAgent {
+        /// The username for authentication.
+        #[cfg_attr(feature = "serde_support", serde(serialize_with = "crate::censor"))]
+        username: String,
+        /// Unix socket of the ssh-agent to delegate signing to, e.g. the
+        /// value of `SSH_AUTH_SOCK`.
+        #[cfg_attr(feature = "serde_support", serde(serialize_with = "crate::censor"))]
+        socket: String,
+        /// Fingerprint (e.g. `SHA256:...`) identifying which identity held
+        /// by the agent to use.
+        #[cfg_attr(feature = "serde_support", serde(serialize_with = "crate::censor"))]
+        fingerprint: String,
+    },
     #[cfg_attr(feature = "serde_support", serde(rename = "snmp"))]
     /// SNMP credentials.
     SNMP {
@@ -176,6 +229,7 @@ impl CredentialType {
                 privacy_password,
                 privacy_algorithm,
             },
+            agent @ CredentialType::USKAgent { .. } => agent,
         })
     }
 }
@@ -185,6 +239,7 @@ impl AsRef<str> for CredentialType {
         match self {
             CredentialType::UP { .. } => "up",
             CredentialType::USK { .. } => "usk",
+            CredentialType::USKAgent { .. } => "usk_agent",
             CredentialType::SNMP { .. } => "snmp",
         }
     }
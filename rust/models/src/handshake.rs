@@ -0,0 +1,72 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use super::{credential::Service, status::Phase};
+
+/// The protocol version spoken by this sensor. Bumped whenever a change is
+/// made that a client cannot safely ignore (as opposed to an additive,
+/// capability-gated change).
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Advertises what a sensor supports so a client can fail fast on an
+/// incompatible version instead of discovering missing features one
+/// 404 at a time.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Handshake {
+    /// The protocol version implemented by this sensor.
+    pub protocol_version: u32,
+    /// The capabilities this sensor currently has enabled.
+    pub capabilities: Capabilities,
+}
+
+/// Optional capabilities a sensor may or may not have enabled, in place of
+/// ad-hoc flags a client would otherwise only learn about by hitting a 404.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Capabilities {
+    /// Whether `GET /scans` is enabled.
+    pub get_scans: bool,
+    /// Credential services this sensor can resolve and use for scanning.
+    pub credential_types: Vec<Service>,
+    /// Phases a scan tracked by this sensor may transition through.
+    pub phases: Vec<Phase>,
+    /// Authentication schemes accepted by this sensor, e.g. `x-api-key` or
+    /// `opaque`. Empty means no authentication is required.
+    pub auth_schemes: Vec<String>,
+    /// Whether scans survive a restart of the sensor.
+    pub persistent_storage: bool,
+}
+
+/// Returned by a client when its own protocol version is incompatible with
+/// the sensor's [`Handshake::protocol_version`].
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct ProtocolIncompatible {
+    /// The protocol version the sensor implements.
+    pub sensor_protocol_version: u32,
+    /// The protocol version the client expected.
+    pub client_protocol_version: u32,
+}
+
+impl std::fmt::Display for ProtocolIncompatible {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "sensor speaks protocol version {} but client requires {}",
+            self.sensor_protocol_version, self.client_protocol_version
+        )
+    }
+}
+
+impl std::error::Error for ProtocolIncompatible {}
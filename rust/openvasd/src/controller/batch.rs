@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! `POST /scans/batch`: applies start/stop/delete to many scans in one
+//! request instead of one HTTP call per scan, reporting each item's outcome
+//! independently so one failing scan doesn't abort the rest of the batch.
+
+use serde::{Deserialize, Serialize};
+
+use super::Context;
+use crate::scan::{ScanDeleter, ScanStarter, ScanStopper};
+
+/// The action side of a [`BatchItem`]. Mirrors `models::Action`
+/// (`start`/`stop`) plus `delete`, which today is only reachable via
+/// `DELETE /scans/{id}`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchAction {
+    /// Starts a stored scan, as `POST /scans/{id}` with `{"action":
+    /// "start"}` does today.
+    Start,
+    /// Stops a running scan.
+    Stop,
+    /// Removes a scan along with its status and results.
+    Delete,
+}
+
+/// One entry of a `POST /scans/batch` request body.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchItem {
+    /// The id of the scan to act on.
+    pub id: String,
+    /// The action to apply to it.
+    pub action: BatchAction,
+}
+
+/// Whether a single [`BatchItem`] succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Ok,
+    Error,
+}
+
+/// One entry of the `POST /scans/batch` response body.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResult {
+    /// The id the corresponding [`BatchItem`] referred to.
+    pub id: String,
+    pub status: BatchStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchResult {
+    fn ok(id: String) -> Self {
+        Self {
+            id,
+            status: BatchStatus::Ok,
+            error: None,
+        }
+    }
+
+    fn err(id: String, error: impl std::fmt::Display) -> Self {
+        Self {
+            id,
+            status: BatchStatus::Error,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Applies every item of `batch` against `ctx`, one after another,
+/// collecting each outcome instead of stopping at the first error.
+pub async fn apply<S, DB>(ctx: &Context<S, DB>, batch: Vec<BatchItem>) -> Vec<BatchResult>
+where
+    S: super::Scanner,
+    DB: crate::storage::Storage,
+{
+    let mut results = Vec::with_capacity(batch.len());
+    for item in batch {
+        let BatchItem { id, action } = item;
+        let result = match action {
+            BatchAction::Start if !ctx.is_unlocked() => BatchResult::err(id, "sensor is locked"),
+            BatchAction::Start => match ctx.db.get_scan(&id).await {
+                Ok(scan) => {
+                    let scan = ctx.unseal_scan_credentials(scan);
+                    match crate::credential_resolver::resolve_scan_credentials(scan, &ctx.credential_resolvers)
+                        .await
+                    {
+                        Ok(scan) => match ctx.scanner.start_scan(scan).await {
+                            Ok(()) => {
+                                ctx.metrics.scan_started();
+                                BatchResult::ok(id)
+                            }
+                            Err(err) => BatchResult::err(id, err),
+                        },
+                        Err(err) => BatchResult::err(id, err),
+                    }
+                }
+                Err(err) => BatchResult::err(id, err),
+            },
+            BatchAction::Stop => match ctx.scanner.stop_scan(&id).await {
+                Ok(()) => {
+                    ctx.metrics.scan_stopped();
+                    BatchResult::ok(id)
+                }
+                Err(err) => BatchResult::err(id, err),
+            },
+            BatchAction::Delete => match ctx.scanner.delete_scan(&id).await {
+                Ok(()) => match ctx.db.delete_scan(&id).await {
+                    Ok(()) => {
+                        ctx.waiters.remove(&id);
+                        ctx.metrics.scan_deleted();
+                        BatchResult::ok(id)
+                    }
+                    Err(err) => BatchResult::err(id, err),
+                },
+                Err(err) => BatchResult::err(id, err),
+            },
+        };
+        results.push(result);
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controller::{ContextBuilder, NoOpScanner},
+        storage::Storage,
+    };
+
+    #[tokio::test]
+    async fn reports_each_item_independently() {
+        let ctx = ContextBuilder::new().scanner(NoOpScanner).build();
+        let id = ctx.db.insert_scan(models::Scan::default()).await.unwrap();
+
+        let results = apply(
+            &ctx,
+            vec![
+                BatchItem {
+                    id: id.clone(),
+                    action: BatchAction::Start,
+                },
+                BatchItem {
+                    id: "does-not-exist".to_string(),
+                    action: BatchAction::Start,
+                },
+            ],
+        )
+        .await;
+
+        assert_eq!(results[0].id, id);
+        assert_eq!(results[0].status, BatchStatus::Ok);
+        assert_eq!(results[1].status, BatchStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn start_is_rejected_while_locked() {
+        let ctx = ContextBuilder::new()
+            .require_unlock(crate::unlock::PassphraseSource::Literal("s3cr3t".to_string()))
+            .scanner(NoOpScanner)
+            .build();
+        let id = ctx.db.insert_scan(models::Scan::default()).await.unwrap();
+
+        let results = apply(
+            &ctx,
+            vec![BatchItem {
+                id: id.clone(),
+                action: BatchAction::Start,
+            }],
+        )
+        .await;
+
+        assert_eq!(results[0].status, BatchStatus::Error);
+    }
+
+    #[tokio::test]
+    async fn delete_removes_the_scan() {
+        let ctx = ContextBuilder::new().scanner(NoOpScanner).build();
+        let id = ctx.db.insert_scan(models::Scan::default()).await.unwrap();
+
+        let results = apply(
+            &ctx,
+            vec![BatchItem {
+                id: id.clone(),
+                action: BatchAction::Delete,
+            }],
+        )
+        .await;
+
+        assert_eq!(results[0].status, BatchStatus::Ok);
+        assert!(ctx.db.get_scan(&id).await.is_err());
+    }
+}
@@ -5,11 +5,17 @@
 use std::{path::PathBuf, sync::RwLock};
 
 use async_trait::async_trait;
+use base64::Engine;
 use storage::DefaultDispatcher;
 
+use super::{metrics::Metrics, poll::Waiters, shutdown::Shutdown};
 use crate::{
+    credential_resolver::CredentialResolvers,
+    crypt::Crypt,
+    opaque_auth::OpaqueAuth,
     response,
     scan::{Error, ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper},
+    unlock::PassphraseSource,
 };
 
 #[derive(Debug, Clone)]
@@ -59,7 +65,10 @@ pub struct ContextBuilder<S, DB, T> {
     result_config: Option<ResultContext>,
     feed_config: Option<FeedContext>,
     api_key: Option<String>,
+    opaque_auth: Option<std::sync::Arc<OpaqueAuth>>,
     enable_get_scans: bool,
+    credential_resolvers: CredentialResolvers,
+    require_unlock: Option<PassphraseSource>,
     marker: std::marker::PhantomData<S>,
     response: response::Response,
 }
@@ -72,8 +81,34 @@ impl<S> ContextBuilder<S, crate::storage::InMemoryStorage<crate::crypt::ChaCha20
             result_config: None,
             feed_config: None,
             api_key: None,
+            opaque_auth: None,
             marker: std::marker::PhantomData,
             enable_get_scans: false,
+            credential_resolvers: CredentialResolvers::new(),
+            require_unlock: None,
+            response: response::Response::default(),
+        }
+    }
+}
+
+impl<S, DB> ContextBuilder<S, DB, NoScanner> {
+    /// Creates a builder backed by an already-constructed `storage`, e.g.
+    /// [`crate::storage::S3Storage`] or [`crate::storage::SqlStorage`],
+    /// instead of the zero-dependency in-memory default `new()` uses.
+    /// Deployments choose durability vs. zero-dependency operation by
+    /// picking which of `new()` or `with_storage(...)` they call.
+    pub fn with_storage(storage: DB) -> Self {
+        Self {
+            scanner: NoScanner,
+            storage,
+            result_config: None,
+            feed_config: None,
+            api_key: None,
+            opaque_auth: None,
+            marker: std::marker::PhantomData,
+            enable_get_scans: false,
+            credential_resolvers: CredentialResolvers::new(),
+            require_unlock: None,
             response: response::Response::default(),
         }
     }
@@ -119,6 +154,34 @@ impl<S, DB, T> ContextBuilder<S, DB, T> {
         self.storage = storage;
         self
     }
+
+    /// Registers a named credential provider, dispatched to at scan start
+    /// for any [`models::CredentialReference`] whose `provider` matches
+    /// `name`. See [`crate::credential_resolver`].
+    pub fn credential_resolver(
+        mut self,
+        name: impl Into<String>,
+        resolver: impl crate::credential_resolver::CredentialResolver + 'static,
+    ) -> Self {
+        self.credential_resolvers = self.credential_resolvers.register(name, resolver);
+        self
+    }
+
+    /// Requires the sensor to be unlocked with a passphrase, obtained from
+    /// `source`, before it will start scans. See [`crate::unlock`].
+    pub fn require_unlock(mut self, source: impl Into<PassphraseSource>) -> Self {
+        self.require_unlock = Some(source.into());
+        self
+    }
+
+    /// Authenticates clients via OPAQUE instead of a plaintext `x-api-key`.
+    /// `server_setup` is the long-lived OPAQUE server setup; reuse the same
+    /// one across restarts so existing client registrations stay valid.
+    pub fn opaque_auth(mut self, server_setup: opaque_ke::ServerSetup<crate::opaque_auth::CipherSuite>) -> Self {
+        self.opaque_auth = Some(std::sync::Arc::new(OpaqueAuth::from_setup(server_setup)));
+        self.response.add_authentication("opaque");
+        self
+    }
 }
 
 impl<S, DB> ContextBuilder<S, DB, NoScanner>
@@ -135,6 +198,9 @@ where
             feed_config,
             api_key,
             enable_get_scans,
+            credential_resolvers,
+            require_unlock,
+            opaque_auth,
             scanner: _,
             marker: _,
             response,
@@ -148,6 +214,9 @@ where
             marker: std::marker::PhantomData,
             api_key,
             enable_get_scans,
+            credential_resolvers,
+            require_unlock,
+            opaque_auth,
             response,
         }
     }
@@ -162,9 +231,18 @@ impl<S, DB> ContextBuilder<S, DB, Scanner<S>> {
             oids: Default::default(),
             result_config: self.result_config,
             feed_config: self.feed_config,
-            abort: Default::default(),
+            shutdown: Shutdown::new(),
             api_key: self.api_key,
+            credential_resolvers: self.credential_resolvers,
+            opaque_auth: self.opaque_auth,
             enable_get_scans: self.enable_get_scans,
+            // An operator that never called `require_unlock` runs in the
+            // historical always-unlocked mode.
+            unlocked: RwLock::new(self.require_unlock.is_none()),
+            require_unlock: self.require_unlock,
+            master_key: RwLock::new(None),
+            metrics: Metrics::new(),
+            waiters: Waiters::new(),
         }
     }
 }
@@ -191,10 +269,153 @@ pub struct Context<S, DB> {
     ///
     /// When none api key is set, no authentication is required.
     pub api_key: Option<String>,
+    /// Named credential providers dispatched to at scan start for any
+    /// `models::CredentialReference`. See [`crate::credential_resolver`].
+    pub credential_resolvers: CredentialResolvers,
+    /// OPAQUE-based authentication state, used instead of `api_key` when
+    /// `.opaque_auth(...)` was configured on the builder.
+    pub opaque_auth: Option<std::sync::Arc<OpaqueAuth>>,
     /// Whether to enable the GET /scans endpoint
     pub enable_get_scans: bool,
-    /// Aborts the background loops
-    pub abort: RwLock<bool>,
+    /// Coordinates a graceful shutdown of the hyper server and the
+    /// background `results::fetch`/`feed::fetch` loops.
+    pub shutdown: Shutdown,
+    /// Where to obtain the passphrase from when unlocking the master key.
+    /// `None` means the sensor does not require unlocking.
+    pub require_unlock: Option<PassphraseSource>,
+    /// The master key derived from the operator passphrase, once unlocked.
+    master_key: RwLock<Option<[u8; 32]>>,
+    /// Whether `start_scan` may proceed. Always `true` when `require_unlock`
+    /// is `None`.
+    unlocked: RwLock<bool>,
+    /// Prometheus metrics registry, served on `GET /metrics`.
+    pub metrics: Metrics,
+    /// Per-scan waiters backing the long-poll mode of the results route.
+    pub waiters: Waiters,
+}
+
+impl<S, DB> Context<S, DB> {
+    /// Marks a password as sealed ciphertext, distinguishing it from a
+    /// password that was stored in plaintext because the sensor was locked
+    /// at insertion time. Not a secret; just a tag.
+    const SEALED_PREFIX: &'static str = "sealed:v1:";
+
+    fn apply_unlock(&self, key: [u8; 32]) {
+        *self.master_key.write().unwrap_or_else(|_| super::quit_on_poison()) = Some(key);
+        *self.unlocked.write().unwrap_or_else(|_| super::quit_on_poison()) = true;
+    }
+
+    /// Whether the sensor is currently unlocked and may start scans.
+    pub fn is_unlocked(&self) -> bool {
+        *self.unlocked.read().unwrap_or_else(|_| super::quit_on_poison())
+    }
+
+    fn master_key(&self) -> Option<[u8; 32]> {
+        *self.master_key.read().unwrap_or_else(|_| super::quit_on_poison())
+    }
+
+    /// Encrypts the password of every embedded (non-reference) credential of
+    /// `scan` under the master key derived by [`Context::unlock`], so it is
+    /// never persisted in plaintext. A sensor with no `require_unlock`
+    /// configured, or one that has not been unlocked yet, stores credentials
+    /// as given: the [`Self::SEALED_PREFIX`] marker is what lets
+    /// [`Context::unseal_scan_credentials`] tell such never-sealed
+    /// credentials apart from real ciphertext later, once the sensor has
+    /// been unlocked.
+    pub fn seal_scan_credentials(&self, scan: models::Scan) -> models::Scan {
+        self.map_embedded_passwords(scan, |crypt, password| {
+            format!(
+                "{}{}",
+                Self::SEALED_PREFIX,
+                base64::engine::general_purpose::STANDARD.encode(crypt.encrypt(password.into_bytes()))
+            )
+        })
+    }
+
+    /// Reverses [`Context::seal_scan_credentials`], called before a scan is
+    /// handed to the scanner. A password without the [`Self::SEALED_PREFIX`]
+    /// marker was stored while the sensor was locked (sealing was a no-op
+    /// then) and is passed through unchanged, rather than being decrypted as
+    /// if it were ciphertext.
+    pub fn unseal_scan_credentials(&self, scan: models::Scan) -> models::Scan {
+        self.map_embedded_passwords(scan, |crypt, password| {
+            let Some(ciphertext) = password.strip_prefix(Self::SEALED_PREFIX) else {
+                return password;
+            };
+            let ciphertext = base64::engine::general_purpose::STANDARD
+                .decode(ciphertext)
+                .unwrap_or_default();
+            String::from_utf8(crypt.decrypt(ciphertext)).unwrap_or_default()
+        })
+    }
+
+    fn map_embedded_passwords(
+        &self,
+        mut scan: models::Scan,
+        f: impl Fn(&crate::crypt::ChaCha20Crypt, String) -> String,
+    ) -> models::Scan {
+        let Some(key) = self.master_key() else {
+            return scan;
+        };
+        let crypt = crate::crypt::ChaCha20Crypt::with_key(key);
+        scan.target.credentials = std::mem::take(&mut scan.target.credentials)
+            .into_iter()
+            .map(|credential| {
+                if credential.is_reference() {
+                    return credential;
+                }
+                credential
+                    .map_password(|password| Ok::<_, std::convert::Infallible>(f(&crypt, password)))
+                    .unwrap()
+            })
+            .collect();
+        scan
+    }
+}
+
+impl<S, DB> Context<S, DB>
+where
+    DB: crate::storage::Storage,
+{
+    /// Generates fresh [`crate::unlock::UnlockMaterial`] for `passphrase`,
+    /// persists it via [`crate::storage::Storage::set_unlock_material`] and
+    /// immediately unlocks the sensor with the derived key. A no-op (beyond
+    /// returning `Ok(())`) when `require_unlock` was never configured.
+    ///
+    /// Call once, on a sensor's first start; a restarted sensor unlocks with
+    /// [`Context::unlock`] instead, which re-derives the key from the
+    /// material persisted here.
+    pub async fn setup_unlock(&self, passphrase: &str) -> Result<(), crate::unlock::Error> {
+        if self.require_unlock.is_none() {
+            return Ok(());
+        }
+        let (material, key) = crate::unlock::setup(passphrase);
+        self.db
+            .set_unlock_material(&material)
+            .await
+            .map_err(|e| crate::unlock::Error::Storage(e.to_string()))?;
+        self.apply_unlock(key);
+        Ok(())
+    }
+
+    /// Unlocks the sensor by loading the [`crate::unlock::UnlockMaterial`]
+    /// persisted by [`Context::setup_unlock`] and re-deriving the master key
+    /// from the configured [`PassphraseSource`], proving the passphrase is
+    /// correct by decrypting the persisted verify blob.
+    pub async fn unlock(&self) -> Result<(), crate::unlock::Error> {
+        let Some(source) = self.require_unlock.as_ref() else {
+            return Ok(());
+        };
+        let material = self
+            .db
+            .get_unlock_material()
+            .await
+            .map_err(|e| crate::unlock::Error::Storage(e.to_string()))?
+            .ok_or(crate::unlock::Error::NotSetUp)?;
+        let key = crate::unlock::unlock(&material, source)?;
+        self.apply_unlock(key);
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -245,3 +466,110 @@ impl Default
         ContextBuilder::new().scanner(Default::default()).build()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedded_credential(password: &str) -> models::Credential {
+        models::Credential {
+            credential_type: models::CredentialType::UP {
+                username: "root".to_string(),
+                password: password.to_string(),
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn without_require_unlock_is_always_unlocked_and_seals_nothing() {
+        let ctx: Context<NoOpScanner, crate::storage::InMemoryStorage<crate::crypt::ChaCha20Crypt>> =
+            ContextBuilder::new().scanner(NoOpScanner).build();
+        assert!(ctx.is_unlocked());
+
+        let mut scan = models::Scan::default();
+        scan.target.credentials = vec![embedded_credential("hunter2")];
+        let sealed = ctx.seal_scan_credentials(scan);
+        assert_eq!(sealed.target.credentials[0].password(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn require_unlock_locks_until_unlocked_and_seals_roundtrip() {
+        let ctx: Context<NoOpScanner, crate::storage::InMemoryStorage<crate::crypt::ChaCha20Crypt>> =
+            ContextBuilder::new()
+                .require_unlock(PassphraseSource::Literal("hunter2-passphrase".to_string()))
+                .scanner(NoOpScanner)
+                .build();
+        assert!(!ctx.is_unlocked());
+
+        let mut scan = models::Scan::default();
+        scan.target.credentials = vec![embedded_credential("hunter2")];
+        // Sealing before unlock leaves the secret untouched: there is no key yet.
+        let sealed = ctx.seal_scan_credentials(scan.clone());
+        assert_eq!(sealed.target.credentials[0].password(), "hunter2");
+
+        ctx.setup_unlock("hunter2-passphrase").await.unwrap();
+        assert!(ctx.is_unlocked());
+        assert!(ctx.db.get_unlock_material().await.unwrap().is_some());
+
+        let sealed = ctx.seal_scan_credentials(scan);
+        assert_ne!(sealed.target.credentials[0].password(), "hunter2");
+        let unsealed = ctx.unseal_scan_credentials(sealed);
+        assert_eq!(unsealed.target.credentials[0].password(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn unlock_re_derives_the_key_from_persisted_material() {
+        // Simulates a sensor restart: `setup_unlock` persisted the material
+        // on first start, and a later `unlock` call (with no material of its
+        // own) must load it back from storage rather than requiring the
+        // caller to have kept it around.
+        let ctx: Context<NoOpScanner, crate::storage::InMemoryStorage<crate::crypt::ChaCha20Crypt>> =
+            ContextBuilder::new()
+                .require_unlock(PassphraseSource::Literal("hunter2-passphrase".to_string()))
+                .scanner(NoOpScanner)
+                .build();
+        ctx.setup_unlock("hunter2-passphrase").await.unwrap();
+
+        *ctx.unlocked.write().unwrap() = false;
+        assert!(!ctx.is_unlocked());
+
+        ctx.unlock().await.unwrap();
+        assert!(ctx.is_unlocked());
+    }
+
+    #[tokio::test]
+    async fn unlock_without_prior_setup_fails() {
+        let ctx: Context<NoOpScanner, crate::storage::InMemoryStorage<crate::crypt::ChaCha20Crypt>> =
+            ContextBuilder::new()
+                .require_unlock(PassphraseSource::Literal("hunter2-passphrase".to_string()))
+                .scanner(NoOpScanner)
+                .build();
+        assert!(matches!(
+            ctx.unlock().await,
+            Err(crate::unlock::Error::NotSetUp)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unsealing_a_scan_stored_while_locked_does_not_touch_its_plaintext_password() {
+        // A scan inserted while locked is stored with `seal_scan_credentials`
+        // a no-op, i.e. plaintext. If the sensor is unlocked afterwards,
+        // `unseal_scan_credentials` must recognize that password was never
+        // sealed instead of treating it as ciphertext.
+        let ctx: Context<NoOpScanner, crate::storage::InMemoryStorage<crate::crypt::ChaCha20Crypt>> =
+            ContextBuilder::new()
+                .require_unlock(PassphraseSource::Literal("hunter2-passphrase".to_string()))
+                .scanner(NoOpScanner)
+                .build();
+
+        let mut scan = models::Scan::default();
+        scan.target.credentials = vec![embedded_credential("hunter2")];
+        let stored = ctx.seal_scan_credentials(scan);
+        assert_eq!(stored.target.credentials[0].password(), "hunter2");
+
+        ctx.setup_unlock("hunter2-passphrase").await.unwrap();
+        let unsealed = ctx.unseal_scan_credentials(stored);
+        assert_eq!(unsealed.target.credentials[0].password(), "hunter2");
+    }
+}
@@ -0,0 +1,350 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! The single hyper entrypoint `make_svc!` dispatches every request through.
+//!
+//! Keeping route matching in one function (rather than one hyper `Service`
+//! per route) means the auth check, the `api-version`/`authentication`
+//! headers and JSON (de-)serialization are handled the same way everywhere,
+//! instead of being re-implemented per route.
+
+use std::sync::Arc;
+
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+use super::{batch, handshake, poll, Context};
+use crate::scan::{ScanDeleter, ScanStarter, ScanStopper};
+use crate::storage::Storage;
+
+fn json_response(status: StatusCode, body: impl serde::Serialize) -> Response<Body> {
+    let body = serde_json::to_vec(&body).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+fn empty_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap_or_else(|_| Response::new(Body::empty()))
+}
+
+fn with_common_headers<S, DB>(mut resp: Response<Body>, ctx: &Context<S, DB>) -> Response<Body> {
+    let headers = resp.headers_mut();
+    headers.insert("api-version", ctx.response.version().parse().unwrap());
+    headers.insert(
+        "authentication",
+        ctx.response.authentication().parse().unwrap(),
+    );
+    resp
+}
+
+/// Returns `true` when `req` carries a valid `X-API-KEY` for `ctx`, a valid
+/// OPAQUE session bearer token, or neither auth mode is configured at all.
+/// When both are configured, only the OPAQUE session token is accepted.
+fn is_authorized<S, DB>(req: &Request<Body>, ctx: &Context<S, DB>) -> bool {
+    if let (Some(expected), None) = (&ctx.api_key, &ctx.opaque_auth) {
+        return req
+            .headers()
+            .get("x-api-key")
+            .and_then(|v| v.to_str().ok())
+            .map(|got| got == expected)
+            .unwrap_or(false);
+    }
+    if let Some(opaque) = &ctx.opaque_auth {
+        return req
+            .headers()
+            .get("x-session-token")
+            .and_then(|v| v.to_str().ok())
+            .map(|got| opaque.is_valid_session(got))
+            .unwrap_or(false);
+    }
+    true
+}
+
+/// Parses `?range=begin-end` into an inclusive `(begin, end)` pair.
+fn parse_range(query: &str) -> Option<(usize, usize)> {
+    query.split('&').find_map(|kv| {
+        let (key, value) = kv.split_once('=')?;
+        if key != "range" {
+            return None;
+        }
+        let (begin, end) = value.split_once('-')?;
+        Some((begin.parse().ok()?, end.parse().ok()?))
+    })
+}
+
+/// Parses `?poll=<token>&timeout=<ms>`, returning `None` when `poll` is
+/// absent (the caller should fall back to the plain range-based read). An
+/// unparsable `poll` value is treated as the initial token; an unparsable or
+/// missing `timeout` defaults to 30s.
+fn parse_poll(query: &str) -> Option<(poll::Token, std::time::Duration)> {
+    let mut present = false;
+    let mut token = poll::Token::default();
+    let mut timeout_ms = 30_000u64;
+    for kv in query.split('&') {
+        let Some((key, value)) = kv.split_once('=') else {
+            continue;
+        };
+        match key {
+            "poll" => {
+                present = true;
+                if let Some(t) = poll::Token::decode(value) {
+                    token = t;
+                }
+            }
+            "timeout" => {
+                if let Ok(ms) = value.parse() {
+                    timeout_ms = ms;
+                }
+            }
+            _ => {}
+        }
+    }
+    present.then(|| (token, std::time::Duration::from_millis(timeout_ms)))
+}
+
+async fn read_body<T: serde::de::DeserializeOwned>(req: Request<Body>) -> Result<T, crate::scan::Error> {
+    let bytes = hyper::body::to_bytes(req.into_body())
+        .await
+        .map_err(|e| crate::scan::Error::Unexpected(e.to_string()))?;
+    serde_json::from_slice(&bytes).map_err(|e| crate::scan::Error::Unexpected(e.to_string()))
+}
+
+/// Dispatches a single hyper request against `ctx`.
+pub async fn entrypoint<S, DB>(
+    req: Request<Body>,
+    ctx: Arc<Context<S, DB>>,
+) -> Result<Response<Body>, crate::scan::Error>
+where
+    S: super::Scanner,
+    DB: Storage,
+{
+    if req.method() == Method::HEAD {
+        return Ok(with_common_headers(empty_response(StatusCode::OK), &ctx));
+    }
+
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or("").to_string();
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    // `/auth/*` issues or exchanges credentials, so it must be reachable
+    // before a client has anything to authenticate with.
+    let is_auth_route = matches!(&method, &Method::POST) && matches!(segments.as_slice(), ["auth", ..]);
+    if !is_auth_route && !is_authorized(&req, &ctx) {
+        return Ok(with_common_headers(
+            empty_response(StatusCode::UNAUTHORIZED),
+            &ctx,
+        ));
+    }
+
+    let resp = match (&method, segments.as_slice()) {
+        (&Method::GET, []) => json_response(StatusCode::OK, handshake::handshake(&ctx)),
+
+        (&Method::GET, ["metrics"]) => Response::builder()
+            .status(StatusCode::OK)
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(Body::from(ctx.metrics.render()))
+            .unwrap_or_else(|_| Response::new(Body::empty())),
+
+        (&Method::POST, ["auth", "register", "start"]) => match &ctx.opaque_auth {
+            Some(opaque) => {
+                let body: crate::opaque_auth::RegisterStartRequest = match read_body(req).await {
+                    Ok(body) => body,
+                    Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+                };
+                match opaque.register_start(&body.client_id, &body.message) {
+                    Ok(message) => json_response(StatusCode::OK, crate::opaque_auth::MessageResponse { message }),
+                    Err(_) => empty_response(StatusCode::BAD_REQUEST),
+                }
+            }
+            None => empty_response(StatusCode::NOT_FOUND),
+        },
+
+        (&Method::POST, ["auth", "register", "finish"]) => match &ctx.opaque_auth {
+            Some(opaque) => {
+                let body: crate::opaque_auth::RegisterFinishRequest = match read_body(req).await {
+                    Ok(body) => body,
+                    Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+                };
+                match opaque.register_finish(&body.client_id, &body.message) {
+                    Ok(()) => empty_response(StatusCode::NO_CONTENT),
+                    Err(_) => empty_response(StatusCode::BAD_REQUEST),
+                }
+            }
+            None => empty_response(StatusCode::NOT_FOUND),
+        },
+
+        (&Method::POST, ["auth", "login", "start"]) => match &ctx.opaque_auth {
+            Some(opaque) => {
+                let body: crate::opaque_auth::LoginStartRequest = match read_body(req).await {
+                    Ok(body) => body,
+                    Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+                };
+                match opaque.login_start(&body.client_id, &body.message) {
+                    Ok((session_id, message)) => json_response(
+                        StatusCode::OK,
+                        crate::opaque_auth::LoginStartResponse { session_id, message },
+                    ),
+                    Err(_) => empty_response(StatusCode::BAD_REQUEST),
+                }
+            }
+            None => empty_response(StatusCode::NOT_FOUND),
+        },
+
+        (&Method::POST, ["auth", "login", "finish"]) => match &ctx.opaque_auth {
+            Some(opaque) => {
+                let body: crate::opaque_auth::LoginFinishRequest = match read_body(req).await {
+                    Ok(body) => body,
+                    Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+                };
+                match opaque.login_finish(&body.session_id, &body.message) {
+                    Ok(token) => json_response(StatusCode::OK, crate::opaque_auth::SessionTokenResponse { token }),
+                    Err(_) => empty_response(StatusCode::UNAUTHORIZED),
+                }
+            }
+            None => empty_response(StatusCode::NOT_FOUND),
+        },
+
+        (&Method::POST, ["scans"]) => {
+            let scan: models::Scan = match read_body(req).await {
+                Ok(scan) => scan,
+                Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+            };
+            let scan = ctx.seal_scan_credentials(scan);
+            match ctx.db.insert_scan(scan).await {
+                Ok(id) => {
+                    ctx.metrics.scan_created();
+                    json_response(StatusCode::CREATED, id)
+                }
+                Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+
+        (&Method::GET, ["scans"]) if ctx.enable_get_scans => match ctx.db.list_scans().await {
+            Ok(ids) => json_response(StatusCode::OK, ids),
+            Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+
+        (&Method::POST, ["scans", "batch"]) => {
+            let items: Vec<batch::BatchItem> = match read_body(req).await {
+                Ok(items) => items,
+                Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+            };
+            json_response(StatusCode::OK, batch::apply(&ctx, items).await)
+        }
+
+        (&Method::GET, ["scans", id]) | (&Method::GET, ["scans", id, "status"]) => {
+            match ctx.db.get_status(id).await {
+                Ok(status) => json_response(StatusCode::OK, status),
+                Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+
+        (&Method::POST, ["scans", id]) => {
+            let action: models::ScanAction = match read_body(req).await {
+                Ok(action) => action,
+                Err(_) => return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx)),
+            };
+            match action.action {
+                models::Action::Start if !ctx.is_unlocked() => {
+                    empty_response(StatusCode::PRECONDITION_FAILED)
+                }
+                models::Action::Start => match ctx.db.get_scan(id).await {
+                    Ok(scan) => {
+                        let scan = ctx.unseal_scan_credentials(scan);
+                        match crate::credential_resolver::resolve_scan_credentials(
+                            scan,
+                            &ctx.credential_resolvers,
+                        )
+                        .await
+                        {
+                            Ok(scan) => match ctx.scanner.start_scan(scan).await {
+                                Ok(()) => {
+                                    ctx.metrics.scan_started();
+                                    empty_response(StatusCode::NO_CONTENT)
+                                }
+                                Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                            },
+                            Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                        }
+                    }
+                    Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                    Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                },
+                models::Action::Stop => match ctx.scanner.stop_scan(id).await {
+                    Ok(()) => {
+                        ctx.metrics.scan_stopped();
+                        empty_response(StatusCode::NO_CONTENT)
+                    }
+                    Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                },
+            }
+        }
+
+        (&Method::DELETE, ["scans", id]) => match ctx.scanner.delete_scan(id).await {
+            Ok(()) => match ctx.db.delete_scan(id).await {
+                Ok(()) => {
+                    ctx.metrics.scan_deleted();
+                    empty_response(StatusCode::NO_CONTENT)
+                }
+                Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+            },
+            Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+
+        (&Method::GET, ["scans", id, "results"]) => {
+            if let Some((token, timeout)) = parse_poll(&query) {
+                match poll::poll(&ctx.db, &ctx.waiters, id, token, timeout).await {
+                    Ok((mut results, next_token)) => {
+                        results.reverse();
+                        ctx.metrics.results_fetched(results.len() as u64);
+                        let mut resp = json_response(StatusCode::OK, results);
+                        if let Ok(value) = next_token.encode().parse() {
+                            resp.headers_mut().insert("x-poll-token", value);
+                        }
+                        resp
+                    }
+                    Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                    Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                }
+            } else {
+                let range = parse_range(&query);
+                match ctx.db.get_results(id, range).await {
+                    Ok(mut results) => {
+                        results.reverse();
+                        ctx.metrics.results_fetched(results.len() as u64);
+                        json_response(StatusCode::OK, results)
+                    }
+                    Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                    Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+                }
+            }
+        }
+
+        (&Method::GET, ["scans", id, "results", idx]) => {
+            let Ok(idx) = idx.parse::<usize>() else {
+                return Ok(with_common_headers(empty_response(StatusCode::BAD_REQUEST), &ctx));
+            };
+            match ctx.db.get_results(id, Some((idx, idx))).await {
+                Ok(mut results) => {
+                    results.reverse();
+                    json_response(StatusCode::OK, results)
+                }
+                Err(crate::storage::Error::NotFound(_)) => empty_response(StatusCode::NOT_FOUND),
+                Err(_) => empty_response(StatusCode::INTERNAL_SERVER_ERROR),
+            }
+        }
+
+        _ => empty_response(StatusCode::NOT_FOUND),
+    };
+
+    Ok(with_common_headers(resp, &ctx))
+}
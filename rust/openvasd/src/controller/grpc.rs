@@ -0,0 +1,197 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! A tonic gRPC service mirroring `entrypoint`'s REST surface: unary
+//! `StartScan`/`StopScan`/`DeleteScan`/`GetStatus` and a server-streaming
+//! `FetchResults` that replaces range-polling `/scans/{id}/results` with a
+//! live feed. `FetchResults` polls the same `DB: Storage` the background
+//! `results::fetch` loop appends into, so no separate pub/sub path is
+//! needed between the two.
+
+use std::{pin::Pin, sync::Arc};
+
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Status as GrpcStatus};
+
+use super::Context;
+use crate::{
+    scan::{ScanDeleter, ScanStarter, ScanStopper},
+    storage::Storage,
+};
+
+tonic::include_proto!("openvasd.scanner");
+
+use scanner_server::Scanner as ScannerRpc;
+pub use scanner_server::ScannerServer;
+
+/// Implements the generated [`ScannerRpc`] trait on top of the same
+/// `Arc<Context<S, DB>>` shared with the hyper service started by
+/// [`super::make_svc`].
+#[derive(Debug)]
+pub struct ScannerService<S, DB> {
+    ctx: Arc<Context<S, DB>>,
+}
+
+impl<S, DB> ScannerService<S, DB> {
+    /// Wraps `ctx` in a gRPC service.
+    pub fn new(ctx: Arc<Context<S, DB>>) -> Self {
+        Self { ctx }
+    }
+}
+
+fn storage_status(err: crate::storage::Error) -> GrpcStatus {
+    match err {
+        crate::storage::Error::NotFound(id) => GrpcStatus::not_found(id),
+        other => GrpcStatus::internal(other.to_string()),
+    }
+}
+
+fn scan_status(err: crate::scan::Error) -> GrpcStatus {
+    GrpcStatus::internal(err.to_string())
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> Result<Vec<u8>, GrpcStatus> {
+    serde_json::to_vec(value).map_err(|e| GrpcStatus::internal(e.to_string()))
+}
+
+#[tonic::async_trait]
+impl<S, DB> ScannerRpc for ScannerService<S, DB>
+where
+    S: super::Scanner + 'static + Send + Sync,
+    DB: Storage + 'static + Send + Sync,
+{
+    async fn start_scan(&self, request: Request<ScanIdRequest>) -> Result<Response<Empty>, GrpcStatus> {
+        let id = request.into_inner().id;
+        if !self.ctx.is_unlocked() {
+            return Err(GrpcStatus::failed_precondition("sensor is locked"));
+        }
+        let scan = self.ctx.db.get_scan(&id).await.map_err(storage_status)?;
+        let scan = self.ctx.unseal_scan_credentials(scan);
+        let scan = crate::credential_resolver::resolve_scan_credentials(scan, &self.ctx.credential_resolvers)
+            .await
+            .map_err(scan_status)?;
+        self.ctx.scanner.start_scan(scan).await.map_err(scan_status)?;
+        self.ctx.metrics.scan_started();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn stop_scan(&self, request: Request<ScanIdRequest>) -> Result<Response<Empty>, GrpcStatus> {
+        let id = request.into_inner().id;
+        self.ctx.scanner.stop_scan(&id).await.map_err(scan_status)?;
+        self.ctx.metrics.scan_stopped();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn delete_scan(&self, request: Request<ScanIdRequest>) -> Result<Response<Empty>, GrpcStatus> {
+        let id = request.into_inner().id;
+        self.ctx.scanner.delete_scan(&id).await.map_err(scan_status)?;
+        self.ctx.db.delete_scan(&id).await.map_err(storage_status)?;
+        self.ctx.metrics.scan_deleted();
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn get_status(&self, request: Request<ScanIdRequest>) -> Result<Response<StatusReply>, GrpcStatus> {
+        let id = request.into_inner().id;
+        let status = self.ctx.db.get_status(&id).await.map_err(storage_status)?;
+        Ok(Response::new(StatusReply {
+            status_json: encode(&status)?,
+        }))
+    }
+
+    type FetchResultsStream = Pin<Box<dyn Stream<Item = Result<ResultReply, GrpcStatus>> + Send + 'static>>;
+
+    /// Streams every result of `id` as it becomes available, polling at the
+    /// same cadence as [`super::results::fetch`] and ending once the scan is
+    /// no longer `Requested` or `Running` (a scan still `Stored`, i.e. never
+    /// started, ends the stream after the first poll) or graceful shutdown
+    /// is triggered.
+    async fn fetch_results(
+        &self,
+        request: Request<ScanIdRequest>,
+    ) -> Result<Response<Self::FetchResultsStream>, GrpcStatus> {
+        let id = request.into_inner().id;
+        let ctx = Arc::clone(&self.ctx);
+        let interval = ctx
+            .result_config
+            .as_ref()
+            .map(|c| c.0)
+            .unwrap_or_else(|| std::time::Duration::from_secs(1));
+        let (tx, rx) = mpsc::channel(16);
+
+        tokio::spawn(async move {
+            let mut next = 0usize;
+            loop {
+                if ctx.shutdown.is_triggered() {
+                    return;
+                }
+                let results = match ctx.db.get_results(&id, Some((next, usize::MAX))).await {
+                    Ok(results) => results,
+                    Err(err) => {
+                        let _ = tx.send(Err(storage_status(err))).await;
+                        return;
+                    }
+                };
+                next += results.len();
+                for result in results {
+                    match encode(&result) {
+                        Ok(result_json) => {
+                            if tx.send(Ok(ResultReply { result_json })).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(err) => {
+                            let _ = tx.send(Err(err)).await;
+                            return;
+                        }
+                    }
+                }
+                match ctx.db.get_status(&id).await {
+                    Ok(status) if status.is_done() => return,
+                    Ok(_) => {}
+                    Err(err) => {
+                        let _ = tx.send(Err(storage_status(err))).await;
+                        return;
+                    }
+                }
+                tokio::time::sleep(interval).await;
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_not_found_maps_to_grpc_not_found() {
+        let status = storage_status(crate::storage::Error::NotFound("abc".to_string()));
+        assert_eq!(status.code(), tonic::Code::NotFound);
+    }
+
+    #[test]
+    fn storage_backend_error_maps_to_grpc_internal() {
+        let status = storage_status(crate::storage::Error::Backend("boom".to_string()));
+        assert_eq!(status.code(), tonic::Code::Internal);
+    }
+
+    #[test]
+    fn scan_errors_map_to_grpc_internal() {
+        let status = scan_status(crate::scan::Error::Unexpected("boom".to_string()));
+        assert_eq!(status.code(), tonic::Code::Internal);
+    }
+
+    #[test]
+    fn encode_round_trips_through_json() {
+        let bytes = encode(&models::Status::default()).unwrap();
+        let decoded: models::Status = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(decoded.status, models::Phase::Stored);
+    }
+
+    // `ScannerService`'s RPC methods themselves are not unit-tested here;
+    // only the backend-agnostic helpers above are.
+}
@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Builds the [`models::Handshake`] a client uses to negotiate protocol
+//! version and discover enabled capabilities up front, instead of hitting a
+//! 404 for every optional route.
+
+use super::Context;
+use crate::storage::Storage;
+
+/// Computes the handshake advertised by `ctx`.
+pub fn handshake<S, DB>(ctx: &Context<S, DB>) -> models::Handshake
+where
+    DB: Storage,
+{
+    let auth_schemes = match (&ctx.api_key, &ctx.opaque_auth) {
+        (_, Some(_)) => vec!["opaque".to_string()],
+        (Some(_), None) => vec!["x-api-key".to_string()],
+        (None, None) => Vec::new(),
+    };
+
+    models::Handshake {
+        protocol_version: models::handshake::PROTOCOL_VERSION,
+        capabilities: models::Capabilities {
+            get_scans: ctx.enable_get_scans,
+            credential_types: vec![
+                models::Service::SSH,
+                models::Service::SMB,
+                models::Service::ESXi,
+                models::Service::SNMP,
+            ],
+            phases: vec![
+                models::Phase::Stored,
+                models::Phase::Requested,
+                models::Phase::Running,
+                models::Phase::Stopped,
+                models::Phase::Failed,
+                models::Phase::Succeeded,
+            ],
+            auth_schemes,
+            persistent_storage: ctx.db.is_persistent(),
+        },
+    }
+}
+
+/// Checks `client_protocol_version` against [`models::handshake::PROTOCOL_VERSION`],
+/// returning a structured incompatibility error instead of letting the
+/// client discover missing features per-request.
+pub fn check_protocol_version(client_protocol_version: u32) -> Result<(), models::ProtocolIncompatible> {
+    if client_protocol_version == models::handshake::PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(models::ProtocolIncompatible {
+            sensor_protocol_version: models::handshake::PROTOCOL_VERSION,
+            client_protocol_version,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::controller::{ContextBuilder, NoOpScanner};
+
+    #[test]
+    fn advertises_no_auth_scheme_when_none_is_configured() {
+        let ctx = ContextBuilder::new().scanner(NoOpScanner).build();
+        assert!(handshake(&ctx).capabilities.auth_schemes.is_empty());
+    }
+
+    #[test]
+    fn advertises_x_api_key_when_configured() {
+        let ctx = ContextBuilder::new()
+            .api_key("secret".to_string())
+            .scanner(NoOpScanner)
+            .build();
+        assert_eq!(handshake(&ctx).capabilities.auth_schemes, vec!["x-api-key".to_string()]);
+    }
+
+    #[test]
+    fn advertises_opaque_when_configured_even_alongside_an_api_key() {
+        let ctx = ContextBuilder::new()
+            .api_key("secret".to_string())
+            .opaque_auth(opaque_ke::ServerSetup::<crate::opaque_auth::CipherSuite>::new(
+                &mut rand::rngs::OsRng,
+            ))
+            .scanner(NoOpScanner)
+            .build();
+        assert_eq!(handshake(&ctx).capabilities.auth_schemes, vec!["opaque".to_string()]);
+    }
+
+    #[test]
+    fn check_protocol_version_accepts_a_matching_version() {
+        assert!(check_protocol_version(models::handshake::PROTOCOL_VERSION).is_ok());
+    }
+
+    #[test]
+    fn check_protocol_version_rejects_a_mismatched_version() {
+        let err = check_protocol_version(models::handshake::PROTOCOL_VERSION + 1).unwrap_err();
+        assert_eq!(err.client_protocol_version, models::handshake::PROTOCOL_VERSION + 1);
+    }
+}
@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Prometheus text-format exposition of scanner and result-fetcher
+//! internals, served on `GET /metrics`.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+    sync::RwLock,
+};
+
+/// Fixed bucket boundaries (in seconds) used for all latency histograms.
+const LATENCY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0];
+
+#[derive(Debug, Default)]
+struct Histogram {
+    buckets: Vec<AtomicU64>,
+    sum_millis: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: LATENCY_BUCKETS.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum_millis: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: std::time::Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, limit) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            if seconds <= *limit {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.sum_millis
+            .fetch_add(duration.as_millis() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Handle to the Prometheus registry for this sensor. Not `Clone`: it is
+/// held once on `Context` and shared through `&Context`.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    scans_created: AtomicU64,
+    scans_started: AtomicU64,
+    scans_stopped: AtomicU64,
+    scans_deleted: AtomicU64,
+    results_fetched: AtomicU64,
+    scans_per_phase: RwLock<HashMap<models::Phase, AtomicI64>>,
+    fetch_loop_latency: Histogram,
+    feed_sync_latency: Histogram,
+    feed_sync_last_success_unix: AtomicI64,
+}
+
+impl Metrics {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self {
+            fetch_loop_latency: Histogram::new(),
+            feed_sync_latency: Histogram::new(),
+            ..Default::default()
+        }
+    }
+
+    /// Records a scan having been created via `POST /scans`.
+    pub fn scan_created(&self) {
+        self.scans_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a scan having been started.
+    pub fn scan_started(&self) {
+        self.scans_started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a scan having been stopped.
+    pub fn scan_stopped(&self) {
+        self.scans_stopped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a scan having been deleted.
+    pub fn scan_deleted(&self) {
+        self.scans_deleted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records `count` results having been fetched for some scan.
+    pub fn results_fetched(&self, count: u64) {
+        self.results_fetched.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Moves a scan from `from` to `to` in the per-phase gauge, e.g. when a
+    /// `results::fetch` iteration observes a status change.
+    pub fn transition_phase(&self, from: Option<models::Phase>, to: models::Phase) {
+        let gauges = self
+            .scans_per_phase
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        if let Some(from) = from {
+            gauges
+                .entry(from)
+                .or_insert_with(|| AtomicI64::new(0))
+                .fetch_sub(1, Ordering::Relaxed);
+        }
+        gauges
+            .entry(to)
+            .or_insert_with(|| AtomicI64::new(0))
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records the wall-clock time a single `results::fetch` iteration took.
+    pub fn fetch_loop_iteration(&self, duration: std::time::Duration) {
+        self.fetch_loop_latency.observe(duration);
+    }
+
+    /// Records a feed sync's duration and marks it as the last successful
+    /// sync.
+    pub fn feed_sync_success(&self, duration: std::time::Duration, unix_timestamp: i64) {
+        self.feed_sync_latency.observe(duration);
+        self.feed_sync_last_success_unix
+            .store(unix_timestamp, Ordering::Relaxed);
+    }
+
+    /// Renders the registry in Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        render_counter(
+            &mut out,
+            "openvasd_scans_created_total",
+            "Total number of scans created.",
+            self.scans_created.load(Ordering::Relaxed),
+        );
+        render_counter(
+            &mut out,
+            "openvasd_scans_started_total",
+            "Total number of scans started.",
+            self.scans_started.load(Ordering::Relaxed),
+        );
+        render_counter(
+            &mut out,
+            "openvasd_scans_stopped_total",
+            "Total number of scans stopped.",
+            self.scans_stopped.load(Ordering::Relaxed),
+        );
+        render_counter(
+            &mut out,
+            "openvasd_scans_deleted_total",
+            "Total number of scans deleted.",
+            self.scans_deleted.load(Ordering::Relaxed),
+        );
+        render_counter(
+            &mut out,
+            "openvasd_results_fetched_total",
+            "Total number of results fetched from the scanner.",
+            self.results_fetched.load(Ordering::Relaxed),
+        );
+
+        out.push_str("# HELP openvasd_scans_current Current number of scans per phase.\n");
+        out.push_str("# TYPE openvasd_scans_current gauge\n");
+        let gauges = self
+            .scans_per_phase
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        for (phase, value) in gauges.iter() {
+            out.push_str(&format!(
+                "openvasd_scans_current{{phase=\"{phase}\"}} {}\n",
+                value.load(Ordering::Relaxed)
+            ));
+        }
+        drop(gauges);
+
+        render_histogram(
+            &mut out,
+            "openvasd_fetch_loop_duration_seconds",
+            "Duration of a single results::fetch iteration.",
+            &self.fetch_loop_latency,
+        );
+        render_histogram(
+            &mut out,
+            "openvasd_feed_sync_duration_seconds",
+            "Duration of a feed sync.",
+            &self.feed_sync_latency,
+        );
+
+        out.push_str(
+            "# HELP openvasd_feed_sync_last_success_timestamp_seconds Unix timestamp of the last successful feed sync.\n",
+        );
+        out.push_str("# TYPE openvasd_feed_sync_last_success_timestamp_seconds gauge\n");
+        out.push_str(&format!(
+            "openvasd_feed_sync_last_success_timestamp_seconds {}\n",
+            self.feed_sync_last_success_unix.load(Ordering::Relaxed)
+        ));
+
+        out
+    }
+}
+
+fn render_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    out.push_str(&format!("# HELP {name} {help}\n"));
+    out.push_str(&format!("# TYPE {name} counter\n"));
+    out.push_str(&format!("{name} {value}\n"));
+}
+
+fn render_histogram(out: &mut String, name: &str, help: &str, histogram: &Histogram) {
+    out.push_str(&format!("# HELP {name} {help}\n"));
+    out.push_str(&format!("# TYPE {name} histogram\n"));
+    let mut cumulative = 0u64;
+    for (bucket, limit) in histogram.buckets.iter().zip(LATENCY_BUCKETS) {
+        cumulative = cumulative.max(bucket.load(Ordering::Relaxed));
+        out.push_str(&format!("{name}_bucket{{le=\"{limit}\"}} {cumulative}\n"));
+    }
+    let count = histogram.count.load(Ordering::Relaxed);
+    out.push_str(&format!("{name}_bucket{{le=\"+Inf\"}} {count}\n"));
+    out.push_str(&format!(
+        "{name}_sum {}\n",
+        histogram.sum_millis.load(Ordering::Relaxed) as f64 / 1000.0
+    ));
+    out.push_str(&format!("{name}_count {count}\n"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_and_gauges_render() {
+        let metrics = Metrics::new();
+        metrics.scan_created();
+        metrics.scan_started();
+        metrics.transition_phase(None, models::Phase::Running);
+        metrics.results_fetched(5);
+
+        let rendered = metrics.render();
+        assert!(rendered.contains("openvasd_scans_created_total 1"));
+        assert!(rendered.contains("openvasd_scans_started_total 1"));
+        assert!(rendered.contains("openvasd_results_fetched_total 5"));
+        assert!(rendered.contains("openvasd_scans_current{phase=\"running\"} 1"));
+    }
+
+    #[test]
+    fn histogram_counts_every_observation() {
+        let histogram = Histogram::new();
+        histogram.observe(std::time::Duration::from_millis(2));
+        histogram.observe(std::time::Duration::from_secs(20));
+        assert_eq!(histogram.count.load(Ordering::Relaxed), 2);
+    }
+}
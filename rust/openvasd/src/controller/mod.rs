@@ -2,16 +2,26 @@
 //
 // SPDX-License-Identifier: GPL-2.0-or-later
 
+pub mod batch;
 mod context;
 mod entry;
 pub mod feed;
+pub mod grpc;
+pub mod handshake;
+pub mod metrics;
+pub mod poll;
 pub mod results;
+pub mod shutdown;
 
 use crate::scan::{ScanDeleter, ScanResultFetcher, ScanStarter, ScanStopper};
 pub use context::{Context, ContextBuilder, NoOpScanner};
 pub use entry::entrypoint;
 
-/// Quits application on an poisoned lock.
+/// Quits application on an poisoned lock. Unrelated to [`shutdown::Shutdown`]:
+/// this covers the data-structure locks guarding `Context` fields, which
+/// have no sensible recovery path once poisoned, whereas `Shutdown` is the
+/// cooperative signal the hyper server and background loops watch to drain
+/// and exit cleanly.
 pub(crate) fn quit_on_poison<T>() -> T {
     tracing::error!("exit because of poisoned lock");
     std::process::exit(1);
@@ -24,8 +34,12 @@ impl<T> Scanner for T where T: ScanStarter + ScanStopper + ScanDeleter + ScanRes
 
 macro_rules! make_svc {
     ($controller:expr) => {{
-        // start background service
+        // re-attach scans a previous process left running, then start the
+        // background services. Each watches `$controller.shutdown` and
+        // drains its current iteration instead of being torn down mid-write
+        // when shutdown is triggered.
         use std::sync::Arc;
+        crate::controller::shutdown::reconcile(Arc::clone(&$controller)).await;
         tokio::spawn(crate::controller::results::fetch(Arc::clone(&$controller)));
         tokio::spawn(crate::controller::feed::fetch(Arc::clone(&$controller)));
 
@@ -43,6 +57,18 @@ macro_rules! make_svc {
 
 pub(crate) use make_svc;
 
+/// Builds the tonic service mirroring `entrypoint`, to be served alongside
+/// the `make_svc` hyper server on its own listen address.
+macro_rules! make_grpc_svc {
+    ($controller:expr) => {
+        crate::controller::grpc::ScannerServer::new(crate::controller::grpc::ScannerService::new(
+            std::sync::Arc::clone($controller),
+        ))
+    };
+}
+
+pub(crate) use make_grpc_svc;
+
 #[cfg(test)]
 mod tests {
     use super::context::Context;
@@ -296,8 +322,7 @@ mod tests {
             let resp = serde_json::from_slice::<models::Status>(&resp).unwrap();
             // would run into an endlessloop if the scan would never finish
             if resp.status == models::Phase::Succeeded {
-                let mut abort = Arc::as_ref(&controller).abort.write().unwrap();
-                *abort = true;
+                Arc::as_ref(&controller).shutdown.trigger();
                 break;
             }
         }
@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Long-poll support for `GET /scans/{id}/results?poll=<token>&timeout=<ms>`.
+//!
+//! [`Waiters`] is a per-scan registry of [`tokio::sync::Notify`] handles,
+//! stored on [`super::Context`] and shared between the results route and the
+//! background `results::fetch` loop: `fetch` calls [`Waiters::notify`] after
+//! appending new results, and the route calls [`poll`] to block until that
+//! happens or `timeout` elapses, instead of the client spinning on
+//! `/scans/{id}/status`.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use base64::Engine;
+use tokio::sync::Notify;
+
+/// An opaque, monotonic cursor: the next `models::Result.id` a client has
+/// not yet seen, i.e. one past the highest id it has already fetched.
+/// Clients carry it as a base64 string; `0` is the initial value and
+/// requests every result from the start.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Token(pub usize);
+
+impl Token {
+    /// Decodes a client-supplied `poll` query value. Returns `None` on any
+    /// malformed input, which callers should treat like no token at all.
+    pub fn decode(raw: &str) -> Option<Self> {
+        let bytes = base64::engine::general_purpose::STANDARD.decode(raw).ok()?;
+        let bytes: [u8; 8] = bytes.try_into().ok()?;
+        Some(Self(u64::from_be_bytes(bytes) as usize))
+    }
+
+    /// Encodes this cursor for the `poll` response header.
+    pub fn encode(self) -> String {
+        base64::engine::general_purpose::STANDARD.encode((self.0 as u64).to_be_bytes())
+    }
+}
+
+/// Registry of per-scan [`Notify`] handles backing the long-poll results
+/// route.
+#[derive(Debug, Default)]
+pub struct Waiters {
+    by_scan: Mutex<HashMap<String, Arc<Notify>>>,
+}
+
+impl Waiters {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn handle(&self, id: &str) -> Arc<Notify> {
+        let mut by_scan = self
+            .by_scan
+            .lock()
+            .unwrap_or_else(|_| super::quit_on_poison());
+        Arc::clone(
+            by_scan
+                .entry(id.to_string())
+                .or_insert_with(|| Arc::new(Notify::new())),
+        )
+    }
+
+    /// Wakes any long-poll waiting on `id`. Called by `results::fetch` right
+    /// after it appends new results for that scan.
+    pub fn notify(&self, id: &str) {
+        self.handle(id).notify_waiters();
+    }
+
+    /// Drops the waiter entry for a scan once it is deleted.
+    pub fn remove(&self, id: &str) {
+        self.by_scan
+            .lock()
+            .unwrap_or_else(|_| super::quit_on_poison())
+            .remove(id);
+    }
+}
+
+/// Resolves one long-poll request: returns immediately if results newer than
+/// `token` already exist, otherwise waits on `waiters` for up to `timeout`
+/// and checks once more before giving up. The returned [`Token`] is the new
+/// cursor the client should echo on its next poll; it is unchanged from
+/// `token` when nothing new was found.
+pub async fn poll<DB>(
+    db: &DB,
+    waiters: &Waiters,
+    id: &str,
+    token: Token,
+    timeout: std::time::Duration,
+) -> Result<(Vec<models::Result>, Token), crate::storage::Error>
+where
+    DB: crate::storage::Storage,
+{
+    async fn since<DB: crate::storage::Storage>(
+        db: &DB,
+        id: &str,
+        token: Token,
+    ) -> Result<Vec<models::Result>, crate::storage::Error> {
+        db.get_results(id, Some((token.0, usize::MAX))).await
+    }
+
+    // Register interest before the initial check: `enable()` arms the
+    // waiter so a `notify_waiters()` call racing with `since()` below is
+    // still observed, instead of only counting notifications that arrive
+    // once we're actually awaiting `notified`.
+    let notify = waiters.handle(id);
+    let notified = notify.notified();
+    tokio::pin!(notified);
+    notified.as_mut().enable();
+
+    let results = since(db, id, token).await?;
+    if !results.is_empty() {
+        let new_token = results.iter().map(|r| r.id).max().map(|id| Token(id + 1)).unwrap_or(token);
+        return Ok((results, new_token));
+    }
+
+    let _ = tokio::time::timeout(timeout, notified).await;
+
+    let results = since(db, id, token).await?;
+    let new_token = results.iter().map(|r| r.id).max().map(|id| Token(id + 1)).unwrap_or(token);
+    Ok((results, new_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InMemoryStorage, Storage};
+
+    #[test]
+    fn token_roundtrips_through_encode_decode() {
+        let token = Token(42);
+        assert_eq!(Token::decode(&token.encode()), Some(token));
+    }
+
+    #[test]
+    fn token_decode_rejects_malformed_input() {
+        assert_eq!(Token::decode("not-base64!"), None);
+        assert_eq!(Token::decode(""), None);
+    }
+
+    #[tokio::test]
+    async fn poll_returns_immediately_when_results_already_exist() {
+        let db = InMemoryStorage::<crate::crypt::ChaCha20Crypt>::default();
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        db.append_results(&id, vec![models::Result { id: 0, ..Default::default() }])
+            .await
+            .unwrap();
+        let waiters = Waiters::new();
+
+        let (results, next_token) = poll(&db, &waiters, &id, Token(0), std::time::Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(next_token, Token(1));
+    }
+
+    #[tokio::test]
+    async fn poll_wakes_up_on_notify_instead_of_waiting_out_the_timeout() {
+        let db = Arc::new(InMemoryStorage::<crate::crypt::ChaCha20Crypt>::default());
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        let waiters = Arc::new(Waiters::new());
+
+        let (db2, waiters2, id2) = (Arc::clone(&db), Arc::clone(&waiters), id.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            db2.append_results(&id2, vec![models::Result { id: 0, ..Default::default() }])
+                .await
+                .unwrap();
+            waiters2.notify(&id2);
+        });
+
+        let started = std::time::Instant::now();
+        let (results, next_token) = poll(&*db, &waiters, &id, Token(0), std::time::Duration::from_secs(30))
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(next_token, Token(1));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn poll_times_out_with_an_unchanged_token_when_nothing_new_arrives() {
+        let db = InMemoryStorage::<crate::crypt::ChaCha20Crypt>::default();
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        let waiters = Waiters::new();
+
+        let (results, next_token) = poll(&db, &waiters, &id, Token(0), std::time::Duration::from_millis(20))
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+        assert_eq!(next_token, Token(0));
+    }
+}
@@ -0,0 +1,213 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Structured shutdown and startup reconciliation.
+//!
+//! [`Shutdown`] replaces the old `Context::abort` boolean flag: the hyper
+//! server started by [`super::make_svc`], `results::fetch` and `feed::fetch`
+//! all watch the same signal, so triggering it lets each drain its current
+//! iteration and persist in-flight scan state instead of being torn down
+//! mid-write by a hard `std::process::exit`.
+//!
+//! [`reconcile`] runs once at startup, before those loops are spawned, and
+//! re-attaches scans a previous process left `Running` or `Requested`.
+
+use tokio::sync::watch;
+
+/// Coordinates a graceful shutdown across the hyper server and the
+/// background loops that share a `Context`. Cheap to clone: every clone
+/// observes the same underlying signal.
+#[derive(Debug, Clone)]
+pub struct Shutdown {
+    tx: std::sync::Arc<watch::Sender<bool>>,
+    rx: watch::Receiver<bool>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        let (tx, rx) = watch::channel(false);
+        Self {
+            tx: std::sync::Arc::new(tx),
+            rx,
+        }
+    }
+}
+
+impl Shutdown {
+    /// Creates a new, untriggered shutdown coordinator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests a graceful shutdown. Idempotent; safe to call from multiple
+    /// places (a signal handler, a poisoned-lock recovery path, ...).
+    pub fn trigger(&self) {
+        let _ = self.tx.send(true);
+    }
+
+    /// Whether shutdown has been requested, without blocking. Background
+    /// loops check this once per iteration in place of the old
+    /// `*ctx.abort.read().unwrap()`.
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Resolves once shutdown has been requested. Intended as the future
+    /// passed to hyper's `Server::with_graceful_shutdown`.
+    pub async fn signal(&self) {
+        let mut rx = self.rx.clone();
+        if *rx.borrow() {
+            return;
+        }
+        let _ = rx.changed().await;
+    }
+}
+
+/// Re-attaches scans a previous process left mid-flight.
+///
+/// For every persisted scan whose [`models::Status::status`] is
+/// [`models::Phase::Running`] or [`models::Phase::Requested`]: if `scanner`
+/// still knows about it, results will keep arriving through the ordinary
+/// `results::fetch` loop once it starts, so nothing else is needed; if the
+/// scanner has no record of it (e.g. the scan engine itself was restarted
+/// too and lost its in-memory state), the scan is marked
+/// [`models::Phase::Failed`] instead of being left to silently strand
+/// results that will never come.
+pub async fn reconcile<S, DB>(ctx: std::sync::Arc<super::Context<S, DB>>)
+where
+    S: super::Scanner,
+    DB: crate::storage::Storage,
+{
+    let scan_ids = match ctx.db.list_scans().await {
+        Ok(ids) => ids,
+        Err(err) => {
+            tracing::error!(%err, "reconciliation: failed to list persisted scans");
+            return;
+        }
+    };
+
+    for id in scan_ids {
+        let status = match ctx.db.get_status(&id).await {
+            Ok(status) => status,
+            Err(err) => {
+                tracing::error!(scan_id = %id, %err, "reconciliation: failed to load status");
+                continue;
+            }
+        };
+        if !matches!(status.status, models::Phase::Running | models::Phase::Requested) {
+            continue;
+        }
+
+        match ctx.scanner.fetch_results(&id).await {
+            Ok(_) => {
+                tracing::info!(scan_id = %id, "re-attaching scan left running across restart");
+            }
+            Err(_) => {
+                tracing::warn!(
+                    scan_id = %id,
+                    "scanner no longer knows this scan, marking it failed after restart"
+                );
+                let failed = models::Status {
+                    status: models::Phase::Failed,
+                    ..status
+                };
+                match ctx.db.update_status(&id, failed).await {
+                    Ok(()) => ctx.metrics.transition_phase(Some(status.status), models::Phase::Failed),
+                    Err(err) => {
+                        tracing::error!(scan_id = %id, %err, "reconciliation: failed to mark scan failed")
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+    use crate::controller::{ContextBuilder, NoOpScanner};
+
+    #[derive(Debug, Clone, Default)]
+    struct ScannerWithoutMemory;
+
+    #[async_trait]
+    impl crate::scan::ScanStarter for ScannerWithoutMemory {
+        async fn start_scan(&self, _: models::Scan) -> Result<(), crate::scan::Error> {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl crate::scan::ScanStopper for ScannerWithoutMemory {
+        async fn stop_scan<I>(&self, _: I) -> Result<(), crate::scan::Error>
+        where
+            I: AsRef<str> + Send,
+        {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl crate::scan::ScanDeleter for ScannerWithoutMemory {
+        async fn delete_scan<I>(&self, _: I) -> Result<(), crate::scan::Error>
+        where
+            I: AsRef<str> + Send,
+        {
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl crate::scan::ScanResultFetcher for ScannerWithoutMemory {
+        async fn fetch_results<I>(&self, _: I) -> Result<crate::scan::FetchResult, crate::scan::Error>
+        where
+            I: AsRef<str> + Send,
+        {
+            Err(crate::scan::Error::Unexpected("scan unknown to scanner".to_string()))
+        }
+    }
+
+    async fn running_scan<S, DB>(ctx: &crate::controller::Context<S, DB>) -> String
+    where
+        DB: crate::storage::Storage,
+    {
+        let id = ctx.db.insert_scan(models::Scan::default()).await.unwrap();
+        ctx.db
+            .update_status(
+                &id,
+                models::Status {
+                    status: models::Phase::Running,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn reconcile_leaves_a_scan_the_scanner_still_knows_about_running() {
+        let ctx = std::sync::Arc::new(ContextBuilder::new().scanner(NoOpScanner).build());
+        let id = running_scan(&*ctx).await;
+
+        reconcile(std::sync::Arc::clone(&ctx)).await;
+
+        let status = ctx.db.get_status(&id).await.unwrap();
+        assert_eq!(status.status, models::Phase::Running);
+    }
+
+    #[tokio::test]
+    async fn reconcile_marks_an_orphaned_scan_as_failed_and_updates_metrics() {
+        let ctx = std::sync::Arc::new(ContextBuilder::new().scanner(ScannerWithoutMemory).build());
+        let id = running_scan(&*ctx).await;
+
+        reconcile(std::sync::Arc::clone(&ctx)).await;
+
+        let status = ctx.db.get_status(&id).await.unwrap();
+        assert_eq!(status.status, models::Phase::Failed);
+        assert!(ctx.metrics.render().contains("openvasd_scans_current{phase=\"failed\"} 1"));
+    }
+}
@@ -0,0 +1,260 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Just-in-time resolution of [`models::CredentialReference`]s so secrets
+//! can stay outside the scan payload and storage entirely.
+
+use std::{collections::HashMap, process::Stdio, sync::Arc};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    process::{Child, ChildStdin, ChildStdout},
+    sync::Mutex,
+};
+
+use crate::scan::Error;
+
+/// Resolves a [`models::CredentialReference`] into the secret it points at.
+///
+/// Called via [`resolve_scan_credentials`] for every credential whose
+/// `reference` field is set, just before the scan is handed to the
+/// underlying scanner's `ScanStarter::start_scan`.
+#[async_trait]
+pub trait CredentialResolver: Send + Sync {
+    /// Looks up the secret for `service`/`key`.
+    async fn resolve(&self, service: &str, key: &str) -> Result<String, Error>;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Request<'a> {
+    Hello,
+    Get { service: &'a str, key: &'a str },
+}
+
+#[derive(Debug, Serialize)]
+struct Envelope<T> {
+    v: u8,
+    #[serde(flatten)]
+    inner: T,
+}
+
+#[derive(Debug, Deserialize)]
+struct Reply {
+    #[allow(dead_code)]
+    v: u8,
+    secret: Option<String>,
+    error: Option<String>,
+}
+
+/// A [`CredentialResolver`] that delegates to an external process speaking
+/// line-delimited JSON over stdin/stdout.
+///
+/// On startup a `{"v":1,"kind":"hello"}` request is sent so the provider can
+/// advertise the services it supports; every subsequent lookup sends
+/// `{"v":1,"kind":"get","service":"ssh","key":"..."}` and expects a single
+/// line reply of either `{"v":1,"secret":"..."}` or `{"v":1,"error":"..."}`.
+pub struct ProcessCredentialResolver {
+    // The child is kept alive for the resolver's lifetime; stdin/stdout are
+    // guarded together so requests and their replies stay interleaved
+    // correctly across concurrent callers.
+    io: Mutex<(ChildStdin, BufReader<ChildStdout>)>,
+    _child: Child,
+}
+
+impl ProcessCredentialResolver {
+    /// Spawns `program` and performs the initial hello handshake.
+    pub async fn spawn(program: &str, args: &[String]) -> std::io::Result<Self> {
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+        let resolver = Self {
+            io: Mutex::new((stdin, stdout)),
+            _child: child,
+        };
+        resolver
+            .send(&Envelope {
+                v: 1,
+                inner: Request::Hello,
+            })
+            .await?;
+        Ok(resolver)
+    }
+
+    async fn send(&self, request: &Envelope<Request<'_>>) -> std::io::Result<Reply> {
+        let mut io = self.io.lock().await;
+        let mut line = serde_json::to_string(request).expect("request is always serializable");
+        line.push('\n');
+        io.0.write_all(line.as_bytes()).await?;
+        io.0.flush().await?;
+        let mut response = String::new();
+        io.1.read_line(&mut response).await?;
+        serde_json::from_str(&response)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[async_trait]
+impl CredentialResolver for ProcessCredentialResolver {
+    async fn resolve(&self, service: &str, key: &str) -> Result<String, Error> {
+        let reply = self
+            .send(&Envelope {
+                v: 1,
+                inner: Request::Get { service, key },
+            })
+            .await
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        if let Some(error) = reply.error {
+            return Err(Error::Unexpected(error));
+        }
+        reply
+            .secret
+            .ok_or_else(|| Error::Unexpected("credential provider returned neither secret nor error".into()))
+    }
+}
+
+/// Resolves the secret of `credential` through `resolver` if it carries a
+/// [`models::CredentialReference`], leaving embedded credentials untouched.
+pub async fn resolve_credential(
+    credential: models::Credential,
+    resolver: &dyn CredentialResolver,
+) -> Result<models::Credential, Error> {
+    let Some(reference) = credential.reference.clone() else {
+        return Ok(credential);
+    };
+    let service = credential.service.as_ref().to_string();
+    let secret = resolver.resolve(&service, &reference.key).await?;
+    credential.map_password(|_| Ok::<_, Error>(secret))
+}
+
+/// Named [`CredentialResolver`]s a sensor can dispatch to, keyed by the
+/// provider name a [`models::CredentialReference::provider`] must match.
+/// Registered via `ContextBuilder::credential_resolver`.
+#[derive(Clone, Default)]
+pub struct CredentialResolvers(HashMap<String, Arc<dyn CredentialResolver>>);
+
+impl std::fmt::Debug for CredentialResolvers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialResolvers")
+            .field("providers", &self.0.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl CredentialResolvers {
+    /// Creates an empty set of resolvers; references will fail to resolve
+    /// until providers are registered.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `resolver` under `provider`.
+    pub fn register(mut self, provider: impl Into<String>, resolver: impl CredentialResolver + 'static) -> Self {
+        self.0.insert(provider.into(), Arc::new(resolver));
+        self
+    }
+}
+
+/// Resolves every reference-backed credential in `scan.target.credentials`
+/// against `resolvers`, selecting the provider named by
+/// [`models::CredentialReference::provider`]. Embedded credentials (no
+/// `reference`) pass through unchanged.
+///
+/// Agent-backed credentials ([`models::CredentialType::USKAgent`]) carry no
+/// secret to resolve; instead they are preflight-checked against the
+/// referenced ssh-agent so a missing identity is reported here rather than
+/// deep inside the scan engine.
+pub async fn resolve_scan_credentials(
+    mut scan: models::Scan,
+    resolvers: &CredentialResolvers,
+) -> Result<models::Scan, Error> {
+    let mut resolved = Vec::with_capacity(scan.target.credentials.len());
+    for credential in std::mem::take(&mut scan.target.credentials) {
+        let credential = match credential.reference.as_ref() {
+            Some(reference) => {
+                let resolver = resolvers.0.get(&reference.provider).ok_or_else(|| {
+                    Error::Unexpected(format!("no credential provider registered for {}", reference.provider))
+                })?;
+                resolve_credential(credential, resolver.as_ref()).await?
+            }
+            None => credential,
+        };
+        preflight_agent_identity(&credential).await?;
+        resolved.push(credential);
+    }
+    scan.target.credentials = resolved;
+    Ok(scan)
+}
+
+/// Confirms the ssh-agent behind a [`models::CredentialType::USKAgent`]
+/// credential still holds the identity it references, by asking it to sign a
+/// throwaway challenge. Any other credential type is a no-op.
+async fn preflight_agent_identity(credential: &models::Credential) -> Result<(), Error> {
+    let models::CredentialType::USKAgent { socket, fingerprint, .. } = &credential.credential_type else {
+        return Ok(());
+    };
+    let mut client = crate::ssh_agent::SshAgentClient::connect(socket).await?;
+    client.sign(fingerprint, b"openvasd-agent-preflight").await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeResolver {
+        secret: &'static str,
+    }
+
+    #[async_trait::async_trait]
+    impl CredentialResolver for FakeResolver {
+        async fn resolve(&self, _service: &str, _key: &str) -> Result<String, Error> {
+            Ok(self.secret.to_string())
+        }
+    }
+
+    fn referenced_credential(provider: &str) -> models::Credential {
+        models::Credential {
+            reference: Some(models::CredentialReference {
+                provider: provider.to_string(),
+                key: "db/ssh".to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_embedded_reference() {
+        let resolver = FakeResolver { secret: "hunter2" };
+        let resolved = resolve_credential(referenced_credential("vault"), &resolver)
+            .await
+            .unwrap();
+        assert_eq!(resolved.password(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn dispatches_by_provider_name() {
+        let resolvers = CredentialResolvers::new().register("vault", FakeResolver { secret: "s3cr3t" });
+        let mut scan = models::Scan::default();
+        scan.target.credentials = vec![referenced_credential("vault")];
+
+        let scan = resolve_scan_credentials(scan, &resolvers).await.unwrap();
+        assert_eq!(scan.target.credentials[0].password(), "s3cr3t");
+    }
+
+    #[tokio::test]
+    async fn unknown_provider_is_an_error() {
+        let resolvers = CredentialResolvers::new();
+        let mut scan = models::Scan::default();
+        scan.target.credentials = vec![referenced_credential("vault")];
+
+        assert!(resolve_scan_credentials(scan, &resolvers).await.is_err());
+    }
+}
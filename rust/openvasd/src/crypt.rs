@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Encryption primitives used to protect sensitive data (e.g. credential
+//! secrets) while they are held by a [`crate::storage::Storage`]
+//! implementation.
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+
+/// Abstracts over a symmetric encryption scheme used to seal secrets before
+/// they are handed to a storage backend and open them again before they are
+/// used.
+///
+/// Implementations are expected to be stateless (or hold only an in-memory
+/// key) so that they can be cloned cheaply and shared between the
+/// `InMemoryStorage` and the background fetch loops.
+pub trait Crypt: Clone + Send + Sync + std::fmt::Debug {
+    /// Encrypts `data`, returning a value that can be persisted as-is.
+    fn encrypt(&self, data: Vec<u8>) -> Vec<u8>;
+    /// Decrypts a value previously returned by [`Crypt::encrypt`].
+    fn decrypt(&self, data: Vec<u8>) -> Vec<u8>;
+}
+
+/// Encrypts data with `XChaCha20Poly1305`, prefixing the ciphertext with the
+/// random nonce used to seal it.
+#[derive(Clone)]
+pub struct ChaCha20Crypt {
+    cipher: XChaCha20Poly1305,
+}
+
+impl std::fmt::Debug for ChaCha20Crypt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChaCha20Crypt").finish()
+    }
+}
+
+impl ChaCha20Crypt {
+    /// Creates a new instance with a randomly generated key.
+    ///
+    /// The key only lives for the lifetime of the process; restarting the
+    /// sensor without a persisted master key means previously encrypted
+    /// values can no longer be decrypted.
+    pub fn new() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self::with_key(key)
+    }
+
+    /// Creates an instance from an explicit 32 byte key, e.g. one derived
+    /// via the unlock subsystem in [`crate::unlock`].
+    pub fn with_key(key: [u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+}
+
+impl Default for ChaCha20Crypt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crypt for ChaCha20Crypt {
+    fn encrypt(&self, data: Vec<u8>) -> Vec<u8> {
+        let mut nonce_bytes = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let nonce = XNonce::from_slice(&nonce_bytes);
+        let mut ciphertext = self
+            .cipher
+            .encrypt(nonce, data.as_ref())
+            .expect("encryption of in-memory data does not fail");
+        let mut result = nonce_bytes.to_vec();
+        result.append(&mut ciphertext);
+        result
+    }
+
+    fn decrypt(&self, data: Vec<u8>) -> Vec<u8> {
+        let (nonce_bytes, ciphertext) = data.split_at(24);
+        let nonce = XNonce::from_slice(nonce_bytes);
+        self.cipher
+            .decrypt(nonce, ciphertext)
+            .expect("decryption of data previously encrypted with the same key")
+    }
+}
@@ -0,0 +1,301 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! OPAQUE-based authentication, replacing a verbatim-stored `x-api-key`
+//! with an augmented PAKE: the sensor only ever holds a password-verifier
+//! envelope, never the client's actual secret.
+//!
+//! Registration has the client send a registration request, the server
+//! answer using its OPRF key, and the client finalize into an envelope the
+//! server stores. Login has the client send a credential request, the
+//! server answer with a credential response derived from the stored
+//! envelope, and the client finalize to produce a mutually-authenticated
+//! session key that authorizes subsequent calls.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
+
+use base64::Engine;
+use opaque_ke::{
+    CredentialFinalization, CredentialRequest, RegistrationRequest, RegistrationUpload, ServerLogin,
+    ServerLoginStartParameters, ServerLoginStartResult, ServerRegistration, ServerSetup,
+};
+use rand::rngs::OsRng;
+
+use crate::scan::Error;
+
+fn decode_b64(message: &str) -> Result<Vec<u8>, Error> {
+    base64::engine::general_purpose::STANDARD
+        .decode(message)
+        .map_err(|e| Error::Unexpected(format!("invalid base64 in opaque message: {e}")))
+}
+
+/// Body of `POST /auth/register/start`.
+#[derive(Debug, serde::Deserialize)]
+pub struct RegisterStartRequest {
+    pub client_id: String,
+    pub message: String,
+}
+
+/// Body of `POST /auth/register/finish`.
+#[derive(Debug, serde::Deserialize)]
+pub struct RegisterFinishRequest {
+    pub client_id: String,
+    pub message: String,
+}
+
+/// Body of `POST /auth/login/start`.
+#[derive(Debug, serde::Deserialize)]
+pub struct LoginStartRequest {
+    pub client_id: String,
+    pub message: String,
+}
+
+/// Response to `POST /auth/login/start`.
+#[derive(Debug, serde::Serialize)]
+pub struct LoginStartResponse {
+    pub session_id: String,
+    pub message: String,
+}
+
+/// Body of `POST /auth/login/finish`.
+#[derive(Debug, serde::Deserialize)]
+pub struct LoginFinishRequest {
+    pub session_id: String,
+    pub message: String,
+}
+
+/// Response carrying the bearer token issued by a completed login, echoed
+/// back on every other route as `x-session-token`.
+#[derive(Debug, serde::Serialize)]
+pub struct SessionTokenResponse {
+    pub token: String,
+}
+
+/// Response to `POST /auth/register/start`.
+#[derive(Debug, serde::Serialize)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+/// The concrete OPAQUE ciphersuite used by this sensor: Ristretto255,
+/// SHA-512 and Argon2 for the memory-hard OPRF-output hardening step.
+pub struct CipherSuite;
+
+impl opaque_ke::CipherSuite for CipherSuite {
+    type OprfCs = opaque_ke::Ristretto255;
+    type KeGroup = opaque_ke::Ristretto255;
+    type KeyExchange = opaque_ke::key_exchange::tripledh::TripleDh;
+    type Ksf = opaque_ke::ksf::Argon2id13;
+}
+
+/// Server-side OPAQUE state: the long-lived server setup, the per-client
+/// registration envelopes and the bearer tokens issued by a completed
+/// login.
+///
+/// Replaces the plaintext `api_key: Option<String>` mode on
+/// `ContextBuilder`; registered via `.opaque_auth(server_setup)`. All
+/// protocol messages cross the HTTP boundary base64-encoded, so
+/// `controller::entry`'s `/auth/*` routes only need to shuttle strings in
+/// and out of JSON without knowing anything about `opaque_ke` itself.
+pub struct OpaqueAuth {
+    server_setup: ServerSetup<CipherSuite>,
+    envelopes: RwLock<HashMap<String, ServerRegistration<CipherSuite>>>,
+    // In-flight logins, keyed by a server-generated session id, waiting for
+    // the client's CredentialFinalization.
+    pending_logins: RwLock<HashMap<String, ServerLogin<CipherSuite>>>,
+    // Bearer tokens (base64 session keys) issued by a completed login.
+    // Checked by `controller::entry::is_authorized` on every other route.
+    active_sessions: RwLock<HashSet<String>>,
+}
+
+impl std::fmt::Debug for OpaqueAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OpaqueAuth").finish()
+    }
+}
+
+impl OpaqueAuth {
+    /// Creates a fresh OPAQUE server, generating a new server setup. The
+    /// setup must be persisted by the caller (e.g. alongside the unlock
+    /// material) so existing client registrations remain valid across
+    /// restarts.
+    pub fn new() -> Self {
+        Self::from_setup(ServerSetup::<CipherSuite>::new(&mut OsRng))
+    }
+
+    /// Restores a server whose setup was generated by a previous `new()`.
+    pub fn from_setup(server_setup: ServerSetup<CipherSuite>) -> Self {
+        Self {
+            server_setup,
+            envelopes: RwLock::default(),
+            pending_logins: RwLock::default(),
+            active_sessions: RwLock::default(),
+        }
+    }
+
+    /// Starts registration of a new client, answering its registration
+    /// request using the server's OPRF key. `message` and the returned
+    /// string are the base64-encoded `opaque_ke` wire messages.
+    pub fn register_start(&self, client_id: &str, message: &str) -> Result<String, Error> {
+        let request = RegistrationRequest::<CipherSuite>::deserialize(&decode_b64(message)?)
+            .map_err(|e| Error::Unexpected(format!("malformed registration request: {e}")))?;
+        let result = opaque_ke::ServerRegistration::<CipherSuite>::start(
+            &self.server_setup,
+            request,
+            client_id.as_bytes(),
+        )
+        .map_err(|e| Error::Unexpected(format!("opaque registration start failed: {e}")))?;
+        Ok(base64::engine::general_purpose::STANDARD.encode(result.message.serialize()))
+    }
+
+    /// Finalizes registration, storing the envelope the client finalized
+    /// into. The server never sees the client's password.
+    pub fn register_finish(&self, client_id: &str, message: &str) -> Result<(), Error> {
+        let upload = RegistrationUpload::<CipherSuite>::deserialize(&decode_b64(message)?)
+            .map_err(|e| Error::Unexpected(format!("malformed registration upload: {e}")))?;
+        let envelope = ServerRegistration::<CipherSuite>::finish(upload);
+        self.envelopes
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .insert(client_id.to_string(), envelope);
+        Ok(())
+    }
+
+    /// Starts a login, returning a session id to correlate the eventual
+    /// `login_finish` call together with the credential response the
+    /// client needs to finalize authentication.
+    pub fn login_start(&self, client_id: &str, message: &str) -> Result<(String, String), Error> {
+        let request = CredentialRequest::<CipherSuite>::deserialize(&decode_b64(message)?)
+            .map_err(|e| Error::Unexpected(format!("malformed credential request: {e}")))?;
+
+        let envelopes = self
+            .envelopes
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        let registration = envelopes
+            .get(client_id)
+            .ok_or_else(|| Error::Unexpected(format!("unknown client {client_id}")))?;
+
+        let ServerLoginStartResult { state, message } = ServerLogin::<CipherSuite>::start(
+            &mut OsRng,
+            &self.server_setup,
+            Some(registration.clone()),
+            request,
+            client_id.as_bytes(),
+            ServerLoginStartParameters::default(),
+        )
+        .map_err(|e| Error::Unexpected(format!("opaque login start failed: {e}")))?;
+        drop(envelopes);
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        self.pending_logins
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .insert(session_id.clone(), state);
+        Ok((session_id, base64::engine::general_purpose::STANDARD.encode(message.serialize())))
+    }
+
+    /// Finalizes a login, issuing the bearer token that authorizes
+    /// subsequent requests for this client. The token is the OPAQUE
+    /// mutually-authenticated session key, base64-encoded.
+    pub fn login_finish(&self, session_id: &str, message: &str) -> Result<String, Error> {
+        let finalization = CredentialFinalization::<CipherSuite>::deserialize(&decode_b64(message)?)
+            .map_err(|e| Error::Unexpected(format!("malformed credential finalization: {e}")))?;
+        let state = self
+            .pending_logins
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .remove(session_id)
+            .ok_or_else(|| Error::Unexpected("unknown or expired login session".into()))?;
+        let result = state
+            .finish(finalization)
+            .map_err(|e| Error::Unexpected(format!("opaque login finish failed: {e}")))?;
+        let token = base64::engine::general_purpose::STANDARD.encode(result.session_key);
+        self.active_sessions
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .insert(token.clone());
+        Ok(token)
+    }
+
+    /// Whether `token` was issued by a completed login and has not been
+    /// revoked since.
+    pub fn is_valid_session(&self, token: &str) -> bool {
+        self.active_sessions
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .contains(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use opaque_ke::{ClientLogin, ClientLoginFinishParameters, ClientRegistration, ClientRegistrationFinishParameters};
+
+    use super::*;
+
+    fn b64(bytes: impl AsRef<[u8]>) -> String {
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    /// Drives both protocol flows end to end through the client-side
+    /// `opaque_ke` API, the way a real client would, and returns the
+    /// resulting bearer token.
+    fn register_and_login(auth: &OpaqueAuth, client_id: &str, password: &str) -> String {
+        let mut rng = rand::rngs::OsRng;
+
+        let registration_start = ClientRegistration::<CipherSuite>::start(&mut rng, password.as_bytes()).unwrap();
+        let registration_response = auth
+            .register_start(client_id, &b64(registration_start.message.serialize()))
+            .unwrap();
+        let registration_response =
+            opaque_ke::RegistrationResponse::<CipherSuite>::deserialize(&decode_b64(&registration_response).unwrap())
+                .unwrap();
+        let registration_finish = registration_start
+            .state
+            .finish(
+                &mut rng,
+                password.as_bytes(),
+                registration_response,
+                ClientRegistrationFinishParameters::default(),
+            )
+            .unwrap();
+        auth.register_finish(client_id, &b64(registration_finish.message.serialize())).unwrap();
+
+        let login_start = ClientLogin::<CipherSuite>::start(&mut rng, password.as_bytes()).unwrap();
+        let (session_id, login_response) = auth
+            .login_start(client_id, &b64(login_start.message.serialize()))
+            .unwrap();
+        let login_response =
+            opaque_ke::CredentialResponse::<CipherSuite>::deserialize(&decode_b64(&login_response).unwrap()).unwrap();
+        let login_finish = login_start
+            .state
+            .finish(password.as_bytes(), login_response, ClientLoginFinishParameters::default())
+            .unwrap();
+        auth.login_finish(&session_id, &b64(login_finish.message.serialize())).unwrap()
+    }
+
+    #[test]
+    fn register_and_login_issues_a_session_the_server_recognizes() {
+        let auth = OpaqueAuth::new();
+        let token = register_and_login(&auth, "alice", "hunter2");
+        assert!(auth.is_valid_session(&token));
+    }
+
+    #[test]
+    fn an_unissued_token_is_not_a_valid_session() {
+        let auth = OpaqueAuth::new();
+        assert!(!auth.is_valid_session(&b64("not-a-real-token")));
+    }
+
+    #[test]
+    fn login_finish_rejects_an_unknown_session_id() {
+        let auth = OpaqueAuth::new();
+        let garbage = b64(vec![0u8; 32]);
+        assert!(auth.login_finish("unknown-session", &garbage).is_err());
+    }
+}
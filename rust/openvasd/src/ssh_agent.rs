@@ -0,0 +1,220 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Minimal client for the ssh-agent wire protocol, used to delegate SSH
+//! authentication for [`models::CredentialType::USKAgent`] credentials so a
+//! private key never has to enter the `Credential` struct or storage.
+//!
+//! Only the two message types needed to locate an identity and sign with it
+//! are implemented; see
+//! <https://tools.ietf.org/id/draft-miller-ssh-agent-04.html> for the full
+//! protocol.
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixStream,
+};
+
+use crate::scan::Error;
+
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+/// One identity advertised by the agent. Only its public key blob is kept;
+/// the comment the agent sends alongside it is discarded.
+struct Identity {
+    public_key: Vec<u8>,
+}
+
+/// A connection to a running ssh-agent, reached over a unix socket.
+pub struct SshAgentClient {
+    stream: UnixStream,
+}
+
+impl SshAgentClient {
+    /// Connects to the agent listening on `socket` (typically the value of
+    /// `SSH_AUTH_SOCK`).
+    pub async fn connect(socket: &str) -> Result<Self, Error> {
+        let stream = UnixStream::connect(socket)
+            .await
+            .map_err(|e| Error::Unexpected(format!("connecting to ssh-agent at {socket}: {e}")))?;
+        Ok(Self { stream })
+    }
+
+    async fn send(&mut self, msg_type: u8, payload: &[u8]) -> Result<(u8, Vec<u8>), Error> {
+        let mut message = Vec::with_capacity(1 + payload.len());
+        message.push(msg_type);
+        message.extend_from_slice(payload);
+        let len = (message.len() as u32).to_be_bytes();
+
+        self.stream
+            .write_all(&len)
+            .await
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        self.stream
+            .write_all(&message)
+            .await
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+
+        let mut len_buf = [0u8; 4];
+        self.stream
+            .read_exact(&mut len_buf)
+            .await
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut body = vec![0u8; len];
+        self.stream
+            .read_exact(&mut body)
+            .await
+            .map_err(|e| Error::Unexpected(e.to_string()))?;
+        let reply_type = body[0];
+        Ok((reply_type, body[1..].to_vec()))
+    }
+
+    /// Lists the identities currently held by the agent.
+    async fn request_identities(&mut self) -> Result<Vec<Identity>, Error> {
+        let (reply_type, body) = self.send(SSH_AGENTC_REQUEST_IDENTITIES, &[]).await?;
+        if reply_type != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(Error::Unexpected(format!(
+                "unexpected ssh-agent reply type {reply_type}, expected identities answer"
+            )));
+        }
+        let mut cursor = &body[..];
+        let count = read_u32(&mut cursor)?;
+        let mut identities = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let public_key = read_blob(&mut cursor)?;
+            let _comment = read_blob(&mut cursor)?;
+            identities.push(Identity { public_key });
+        }
+        Ok(identities)
+    }
+
+    /// Finds the identity matching `fingerprint` (a `SHA256:...` digest of
+    /// the public key blob) and asks the agent to sign `data` with it.
+    pub async fn sign(&mut self, fingerprint: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let identities = self.request_identities().await?;
+        let identity = identities
+            .into_iter()
+            .find(|i| public_key_fingerprint(&i.public_key) == fingerprint)
+            .ok_or_else(|| Error::Unexpected(format!("no identity matching fingerprint {fingerprint}")))?;
+
+        let mut payload = Vec::new();
+        write_blob(&mut payload, &identity.public_key);
+        write_blob(&mut payload, data);
+        payload.extend_from_slice(&0u32.to_be_bytes()); // no signature flags
+
+        let (reply_type, body) = self.send(SSH_AGENTC_SIGN_REQUEST, &payload).await?;
+        if reply_type != SSH_AGENT_SIGN_RESPONSE {
+            return Err(Error::Unexpected(format!(
+                "unexpected ssh-agent reply type {reply_type}, expected sign response"
+            )));
+        }
+        let mut cursor = &body[..];
+        read_blob(&mut cursor)
+    }
+}
+
+fn public_key_fingerprint(public_key: &[u8]) -> String {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+    let digest = Sha256::digest(public_key);
+    // OpenSSH's own `ssh-keygen -lf`/`ssh-add -l` output is unpadded base64;
+    // padding here would produce a fingerprint real OpenSSH tooling never
+    // emits and can't match against.
+    format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(digest)
+    )
+}
+
+fn read_u32(cursor: &mut &[u8]) -> Result<u32, Error> {
+    if cursor.len() < 4 {
+        return Err(Error::Unexpected("truncated ssh-agent message".into()));
+    }
+    let (head, tail) = cursor.split_at(4);
+    *cursor = tail;
+    Ok(u32::from_be_bytes(head.try_into().unwrap()))
+}
+
+fn read_blob(cursor: &mut &[u8]) -> Result<Vec<u8>, Error> {
+    let len = read_u32(cursor)? as usize;
+    if cursor.len() < len {
+        return Err(Error::Unexpected("truncated ssh-agent message".into()));
+    }
+    let (head, tail) = cursor.split_at(len);
+    *cursor = tail;
+    Ok(head.to_vec())
+}
+
+fn write_blob(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    buf.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    /// Speaks just enough of the agent side of the protocol to drive a real
+    /// [`SshAgentClient`] through [`SshAgentClient::sign`]: one identity,
+    /// with a canned signature returned for any sign request.
+    async fn serve_one_connection(listener: UnixListener, public_key: Vec<u8>, signature: Vec<u8>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let request = read_message(&mut stream).await;
+        assert_eq!(request.0, SSH_AGENTC_REQUEST_IDENTITIES);
+        let mut identities_answer = vec![0, 0, 0, 1];
+        write_blob(&mut identities_answer, &public_key);
+        write_blob(&mut identities_answer, b"test identity");
+        write_message(&mut stream, SSH_AGENT_IDENTITIES_ANSWER, &identities_answer).await;
+
+        let request = read_message(&mut stream).await;
+        assert_eq!(request.0, SSH_AGENTC_SIGN_REQUEST);
+        let mut sign_response = Vec::new();
+        write_blob(&mut sign_response, &signature);
+        write_message(&mut stream, SSH_AGENT_SIGN_RESPONSE, &sign_response).await;
+    }
+
+    async fn read_message(stream: &mut UnixStream) -> (u8, Vec<u8>) {
+        use tokio::io::AsyncReadExt;
+        let mut len_buf = [0u8; 4];
+        stream.read_exact(&mut len_buf).await.unwrap();
+        let len = u32::from_be_bytes(len_buf) as usize;
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body).await.unwrap();
+        (body[0], body[1..].to_vec())
+    }
+
+    async fn write_message(stream: &mut UnixStream, msg_type: u8, payload: &[u8]) {
+        use tokio::io::AsyncWriteExt;
+        let mut message = vec![msg_type];
+        message.extend_from_slice(payload);
+        stream.write_all(&(message.len() as u32).to_be_bytes()).await.unwrap();
+        stream.write_all(&message).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn sign_finds_the_identity_and_returns_the_agents_signature() {
+        let dir = std::env::temp_dir().join(format!("openvasd-ssh-agent-test-{}", uuid::Uuid::new_v4()));
+        let listener = UnixListener::bind(&dir).unwrap();
+
+        let public_key = b"fake-ed25519-public-key".to_vec();
+        let signature = b"fake-signature".to_vec();
+        let fingerprint = public_key_fingerprint(&public_key);
+
+        let server = tokio::spawn(serve_one_connection(listener, public_key, signature.clone()));
+
+        let mut client = SshAgentClient::connect(dir.to_str().unwrap()).await.unwrap();
+        let result = client.sign(&fingerprint, b"data to sign").await.unwrap();
+        assert_eq!(result, signature);
+
+        server.await.unwrap();
+        let _ = std::fs::remove_file(&dir);
+    }
+}
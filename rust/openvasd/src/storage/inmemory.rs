@@ -0,0 +1,183 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+use std::{collections::HashMap, sync::RwLock};
+
+use async_trait::async_trait;
+
+use crate::{crypt::Crypt, unlock::UnlockMaterial};
+
+use super::{Error, Storage};
+
+struct Entry {
+    scan: models::Scan,
+    status: models::Status,
+    results: Vec<models::Result>,
+}
+
+/// The zero-dependency default [`Storage`] backend: everything lives in a
+/// `RwLock<HashMap<..>>` and is lost when the process exits.
+///
+/// `C` is a [`Crypt`] implementation held by the storage; the storage itself
+/// does not use it, and stores scans exactly as given (credential sealing
+/// happens in `Context::seal_scan_credentials` before a scan reaches it).
+#[derive(Debug, Default)]
+pub struct InMemoryStorage<C> {
+    scans: RwLock<HashMap<String, Entry>>,
+    unlock_material: RwLock<Option<UnlockMaterial>>,
+    // Reserved for sealing credential secrets at rest; see the unlock
+    // subsystem for how a master key is derived and wired in.
+    #[allow(dead_code)]
+    crypt: C,
+}
+
+impl std::fmt::Debug for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Entry").field("status", &self.status).finish()
+    }
+}
+
+impl<C> InMemoryStorage<C>
+where
+    C: Crypt,
+{
+    /// Creates a new, empty storage holding `crypt`.
+    pub fn new(crypt: C) -> Self {
+        Self {
+            scans: RwLock::default(),
+            unlock_material: RwLock::default(),
+            crypt,
+        }
+    }
+}
+
+#[async_trait]
+impl<C> Storage for InMemoryStorage<C>
+where
+    C: Crypt,
+{
+    async fn insert_scan(&self, scan: models::Scan) -> Result<String, Error> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let scan = models::Scan { scan_id: id.clone(), ..scan };
+        let mut scans = self
+            .scans
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        scans.insert(
+            id.clone(),
+            Entry {
+                scan,
+                status: models::Status::default(),
+                results: Vec::new(),
+            },
+        );
+        Ok(id)
+    }
+
+    async fn get_scan(&self, id: &str) -> Result<models::Scan, Error> {
+        let scans = self
+            .scans
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        scans
+            .get(id)
+            .map(|e| e.scan.clone())
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    async fn delete_scan(&self, id: &str) -> Result<(), Error> {
+        let mut scans = self
+            .scans
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        scans
+            .remove(id)
+            .map(|_| ())
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    async fn list_scans(&self) -> Result<Vec<String>, Error> {
+        let scans = self
+            .scans
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        Ok(scans.keys().cloned().collect())
+    }
+
+    async fn update_status(&self, id: &str, status: models::Status) -> Result<(), Error> {
+        let mut scans = self
+            .scans
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        let entry = scans
+            .get_mut(id)
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        entry.status = status;
+        Ok(())
+    }
+
+    async fn get_status(&self, id: &str) -> Result<models::Status, Error> {
+        let scans = self
+            .scans
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        scans
+            .get(id)
+            .map(|e| e.status.clone())
+            .ok_or_else(|| Error::NotFound(id.to_string()))
+    }
+
+    async fn append_results(&self, id: &str, results: Vec<models::Result>) -> Result<(), Error> {
+        let mut scans = self
+            .scans
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        let entry = scans
+            .get_mut(id)
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        entry.results.extend(results);
+        Ok(())
+    }
+
+    async fn get_results(
+        &self,
+        id: &str,
+        range: Option<(usize, usize)>,
+    ) -> Result<Vec<models::Result>, Error> {
+        let scans = self
+            .scans
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison());
+        let entry = scans.get(id).ok_or_else(|| Error::NotFound(id.to_string()))?;
+        Ok(match range {
+            Some((begin, end)) => entry
+                .results
+                .iter()
+                .filter(|r| r.id >= begin && r.id <= end)
+                .cloned()
+                .collect(),
+            None => entry.results.clone(),
+        })
+    }
+
+    async fn set_unlock_material(&self, material: &UnlockMaterial) -> Result<(), Error> {
+        *self
+            .unlock_material
+            .write()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison()) = Some(material.clone());
+        Ok(())
+    }
+
+    async fn get_unlock_material(&self) -> Result<Option<UnlockMaterial>, Error> {
+        Ok(self
+            .unlock_material
+            .read()
+            .unwrap_or_else(|_| crate::controller::quit_on_poison())
+            .clone())
+    }
+
+    fn is_persistent(&self) -> bool {
+        false
+    }
+}
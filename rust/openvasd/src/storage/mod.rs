@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Persistence for scans, their status and their results.
+//!
+//! The [`Storage`] trait is the single extension point a [`crate::controller::Context`]
+//! is generic over (the `DB` type parameter). [`inmemory::InMemoryStorage`] is
+//! the zero-dependency default used when nothing else is configured;
+//! [`s3::S3Storage`] backs the same trait with an S3-compatible object store
+//! so a sensor can restart without losing in-flight scans, and
+//! [`sql::SqlStorage`] does the same against Postgres or SQLite.
+
+pub mod inmemory;
+pub mod s3;
+pub mod sql;
+
+use async_trait::async_trait;
+
+pub use inmemory::InMemoryStorage;
+pub use s3::S3Storage;
+pub use sql::SqlStorage;
+
+use crate::unlock::UnlockMaterial;
+
+/// Error produced by a [`Storage`] implementation.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The requested scan does not exist.
+    #[error("scan {0} not found")]
+    NotFound(String),
+    /// The backend could not be reached or returned an unexpected response.
+    #[error("storage backend error: {0}")]
+    Backend(String),
+    /// A stored value could not be (de-)serialized.
+    #[error("(de-)serialization error: {0}")]
+    Serialization(String),
+}
+
+/// Persists scans, their [`models::Status`] and the [`models::Result`]s
+/// produced while they run.
+///
+/// Implementations are shared behind an `Arc` and must therefore be `Send +
+/// Sync`; interior mutability (a lock, a connection pool, ...) is expected.
+#[async_trait]
+pub trait Storage {
+    /// Stores a new scan under a freshly generated id and returns that id.
+    async fn insert_scan(&self, scan: models::Scan) -> Result<String, Error>;
+
+    /// Loads a previously stored scan.
+    async fn get_scan(&self, id: &str) -> Result<models::Scan, Error>;
+
+    /// Removes a scan along with its status and results.
+    async fn delete_scan(&self, id: &str) -> Result<(), Error>;
+
+    /// Returns the ids of all scans currently tracked.
+    async fn list_scans(&self) -> Result<Vec<String>, Error>;
+
+    /// Replaces the [`models::Status`] of a scan.
+    async fn update_status(&self, id: &str, status: models::Status) -> Result<(), Error>;
+
+    /// Returns the last known [`models::Status`] of a scan.
+    async fn get_status(&self, id: &str) -> Result<models::Status, Error>;
+
+    /// Appends results produced by a scan, preserving the order they were
+    /// fetched in.
+    async fn append_results(&self, id: &str, results: Vec<models::Result>) -> Result<(), Error>;
+
+    /// Returns the results of a scan, optionally restricted to `range`
+    /// (inclusive on both ends, mirroring the `?range=begin-end` query
+    /// parameter of the results route).
+    async fn get_results(
+        &self,
+        id: &str,
+        range: Option<(usize, usize)>,
+    ) -> Result<Vec<models::Result>, Error>;
+
+    /// Persists the [`UnlockMaterial`] set up for this sensor, overwriting
+    /// any material set up previously. See [`crate::unlock`].
+    async fn set_unlock_material(&self, material: &UnlockMaterial) -> Result<(), Error>;
+
+    /// Returns the [`UnlockMaterial`] persisted by [`Self::set_unlock_material`],
+    /// or `None` if the sensor has never been set up.
+    async fn get_unlock_material(&self) -> Result<Option<UnlockMaterial>, Error>;
+
+    /// Whether a scan stored by this backend survives a sensor restart.
+    /// Advertised to clients via [`crate::controller::handshake::handshake`]
+    /// so they know whether in-flight scans need to be tracked independently.
+    fn is_persistent(&self) -> bool;
+}
@@ -0,0 +1,258 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! A [`Storage`] backend persisting scans to an S3-compatible object store.
+//!
+//! Because the client is configured with an explicit endpoint it works
+//! against AWS as well as self-hosted stores such as MinIO or Garage.
+
+use async_trait::async_trait;
+use aws_sdk_s3::{primitives::ByteStream, Client};
+
+use crate::unlock::UnlockMaterial;
+
+use super::{Error, Storage};
+
+/// Configuration needed to reach the bucket a sensor should persist scans
+/// into.
+#[derive(Debug, Clone)]
+pub struct S3StorageConfig {
+    /// Name of the bucket scans are stored in.
+    pub bucket: String,
+    /// Endpoint of the object store, e.g. `https://minio.example.internal`.
+    /// Left unset to use the default AWS endpoint resolution.
+    pub endpoint: Option<String>,
+    /// Region advertised to the SDK. Required by some S3-compatible stores
+    /// even when `endpoint` is set.
+    pub region: String,
+    /// Static access key. When unset the SDK's default credential chain is
+    /// used instead.
+    pub access_key_id: Option<String>,
+    /// Static secret key, paired with `access_key_id`.
+    pub secret_access_key: Option<String>,
+}
+
+/// Persists scans, status and results as JSON objects in an S3 bucket.
+///
+/// Keys are namespaced per scan: `scans/{id}/scan.json`,
+/// `scans/{id}/status.json` and one `scans/{id}/results/{result_id}.json`
+/// per result, so a restarted sensor can re-hydrate a scan by reading the
+/// objects under its id, and concurrent `append_results` calls land on
+/// distinct keys instead of racing on a single read-modify-write object.
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    client: Client,
+    bucket: String,
+}
+
+impl S3Storage {
+    /// Builds the client described by `config` and verifies the bucket is
+    /// reachable.
+    pub async fn new(config: S3StorageConfig) -> Result<Self, Error> {
+        let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new(config.region));
+        if let (Some(key), Some(secret)) = (&config.access_key_id, &config.secret_access_key) {
+            loader = loader.credentials_provider(aws_sdk_s3::config::Credentials::new(
+                key.clone(),
+                secret.clone(),
+                None,
+                None,
+                "openvasd-static",
+            ));
+        }
+        let sdk_config = loader.load().await;
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&sdk_config);
+        if let Some(endpoint) = &config.endpoint {
+            s3_config = s3_config.endpoint_url(endpoint).force_path_style(true);
+        }
+        let client = Client::from_conf(s3_config.build());
+        Ok(Self {
+            client,
+            bucket: config.bucket,
+        })
+    }
+
+    fn scan_key(id: &str) -> String {
+        format!("scans/{id}/scan.json")
+    }
+
+    fn status_key(id: &str) -> String {
+        format!("scans/{id}/status.json")
+    }
+
+    fn results_prefix(id: &str) -> String {
+        format!("scans/{id}/results/")
+    }
+
+    fn result_key(id: &str, result_id: usize) -> String {
+        format!("scans/{id}/results/{result_id}.json")
+    }
+
+    fn unlock_material_key() -> &'static str {
+        "unlock/material.json"
+    }
+
+    /// Lists every object key under `prefix`, following `list_objects_v2`'s
+    /// continuation token until the bucket reports no more pages.
+    async fn list_all_keys(&self, prefix: &str) -> Result<Vec<String>, Error> {
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let mut request = self.client.list_objects_v2().bucket(&self.bucket).prefix(prefix);
+            if let Some(token) = continuation_token.take() {
+                request = request.continuation_token(token);
+            }
+            let resp = request.send().await.map_err(|e| Error::Backend(e.to_string()))?;
+            keys.extend(resp.contents().iter().filter_map(|o| o.key()).map(str::to_string));
+            if resp.is_truncated().unwrap_or(false) {
+                continuation_token = resp.next_continuation_token().map(str::to_string);
+                if continuation_token.is_none() {
+                    break;
+                }
+            } else {
+                break;
+            }
+        }
+        Ok(keys)
+    }
+
+    async fn put_json<T: serde::Serialize>(&self, key: &str, value: &T) -> Result<(), Error> {
+        let body = serde_json::to_vec(value).map_err(|e| Error::Serialization(e.to_string()))?;
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, id: &str, key: &str) -> Result<T, Error> {
+        let resp = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|_| Error::NotFound(id.to_string()))?;
+        let bytes = resp
+            .body
+            .collect()
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .into_bytes();
+        serde_json::from_slice(&bytes).map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn insert_scan(&self, scan: models::Scan) -> Result<String, Error> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let scan = models::Scan { scan_id: id.clone(), ..scan };
+        self.put_json(&Self::scan_key(&id), &scan).await?;
+        self.put_json(&Self::status_key(&id), &models::Status::default())
+            .await?;
+        Ok(id)
+    }
+
+    async fn get_scan(&self, id: &str) -> Result<models::Scan, Error> {
+        self.get_json(id, &Self::scan_key(id)).await
+    }
+
+    async fn delete_scan(&self, id: &str) -> Result<(), Error> {
+        let mut keys = vec![Self::scan_key(id), Self::status_key(id)];
+        keys.extend(self.list_all_keys(&Self::results_prefix(id)).await?);
+        for key in keys {
+            self.client
+                .delete_object()
+                .bucket(&self.bucket)
+                .key(key)
+                .send()
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn list_scans(&self) -> Result<Vec<String>, Error> {
+        let keys = self.list_all_keys("scans/").await?;
+        Ok(keys
+            .iter()
+            .filter_map(|k| k.strip_prefix("scans/")?.split('/').next())
+            .map(str::to_string)
+            .collect::<std::collections::HashSet<_>>()
+            .into_iter()
+            .collect())
+    }
+
+    async fn update_status(&self, id: &str, status: models::Status) -> Result<(), Error> {
+        self.put_json(&Self::status_key(id), &status).await
+    }
+
+    async fn get_status(&self, id: &str) -> Result<models::Status, Error> {
+        self.get_json(id, &Self::status_key(id)).await
+    }
+
+    async fn append_results(&self, id: &str, results: Vec<models::Result>) -> Result<(), Error> {
+        for result in &results {
+            self.put_json(&Self::result_key(id, result.id), result).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_results(
+        &self,
+        id: &str,
+        range: Option<(usize, usize)>,
+    ) -> Result<Vec<models::Result>, Error> {
+        let keys = self.list_all_keys(&Self::results_prefix(id)).await?;
+        let mut results = Vec::with_capacity(keys.len());
+        for key in keys {
+            let result: models::Result = self.get_json(id, &key).await?;
+            if let Some((begin, end)) = range {
+                if result.id < begin || result.id > end {
+                    continue;
+                }
+            }
+            results.push(result);
+        }
+        results.sort_by_key(|r| r.id);
+        Ok(results)
+    }
+
+    async fn set_unlock_material(&self, material: &UnlockMaterial) -> Result<(), Error> {
+        self.put_json(Self::unlock_material_key(), material).await
+    }
+
+    async fn get_unlock_material(&self) -> Result<Option<UnlockMaterial>, Error> {
+        let resp = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(Self::unlock_material_key())
+            .send()
+            .await;
+        let resp = match resp {
+            Ok(resp) => resp,
+            Err(_) => return Ok(None),
+        };
+        let bytes = resp
+            .body
+            .collect()
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .into_bytes();
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| Error::Serialization(e.to_string()))
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! A [`Storage`] backend persisting scans, status and results in a SQL
+//! database (Postgres or SQLite, whichever `connection_url` points at),
+//! so a restarted or replicated sensor doesn't lose in-flight scans the way
+//! [`super::InMemoryStorage`] does.
+
+use async_trait::async_trait;
+use sqlx::{any::AnyPoolOptions, AnyPool, Row};
+
+use crate::unlock::UnlockMaterial;
+
+use super::{Error, Storage};
+
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS scans (
+        id TEXT PRIMARY KEY,
+        scan_json TEXT NOT NULL,
+        status_json TEXT NOT NULL
+    )",
+    "CREATE TABLE IF NOT EXISTS results (
+        scan_id TEXT NOT NULL REFERENCES scans(id),
+        result_id INTEGER NOT NULL,
+        result_json TEXT NOT NULL,
+        PRIMARY KEY (scan_id, result_id)
+    )",
+    "CREATE INDEX IF NOT EXISTS results_scan_id_idx ON results (scan_id, result_id)",
+    "CREATE TABLE IF NOT EXISTS unlock_material (
+        id INTEGER PRIMARY KEY,
+        material_json TEXT NOT NULL
+    )",
+];
+
+/// A SQL-backed [`Storage`] implementation, pooled via `sqlx`'s `Any` driver
+/// so the same code path serves both Postgres and SQLite connection URLs.
+#[derive(Debug, Clone)]
+pub struct SqlStorage {
+    pool: AnyPool,
+}
+
+impl SqlStorage {
+    /// Connects to `connection_url` (e.g. `postgres://...` or
+    /// `sqlite://openvasd.db`) and runs the schema migrations, creating
+    /// tables that don't exist yet.
+    pub async fn new(connection_url: &str) -> Result<Self, Error> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(10)
+            .connect(connection_url)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        for migration in MIGRATIONS {
+            sqlx::query(migration)
+                .execute(&pool)
+                .await
+                .map_err(|e| Error::Backend(e.to_string()))?;
+        }
+        Ok(Self { pool })
+    }
+
+    fn decode_json<T: serde::de::DeserializeOwned>(raw: &str) -> Result<T, Error> {
+        serde_json::from_str(raw).map_err(|e| Error::Serialization(e.to_string()))
+    }
+
+    fn encode_json<T: serde::Serialize>(value: &T) -> Result<String, Error> {
+        serde_json::to_string(value).map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl Storage for SqlStorage {
+    async fn insert_scan(&self, scan: models::Scan) -> Result<String, Error> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let scan = models::Scan { scan_id: id.clone(), ..scan };
+        let scan_json = Self::encode_json(&scan)?;
+        let status_json = Self::encode_json(&models::Status::default())?;
+        sqlx::query("INSERT INTO scans (id, scan_json, status_json) VALUES ($1, $2, $3)")
+            .bind(&id)
+            .bind(scan_json)
+            .bind(status_json)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(id)
+    }
+
+    async fn get_scan(&self, id: &str) -> Result<models::Scan, Error> {
+        let row = sqlx::query("SELECT scan_json FROM scans WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        Self::decode_json(row.try_get::<String, _>("scan_json").map_err(|e| Error::Backend(e.to_string()))?.as_str())
+    }
+
+    async fn delete_scan(&self, id: &str) -> Result<(), Error> {
+        sqlx::query("DELETE FROM results WHERE scan_id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        let deleted = sqlx::query("DELETE FROM scans WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        if deleted.rows_affected() == 0 {
+            return Err(Error::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn list_scans(&self) -> Result<Vec<String>, Error> {
+        let rows = sqlx::query("SELECT id FROM scans")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        rows.into_iter()
+            .map(|row| row.try_get::<String, _>("id").map_err(|e| Error::Backend(e.to_string())))
+            .collect()
+    }
+
+    async fn update_status(&self, id: &str, status: models::Status) -> Result<(), Error> {
+        let status_json = Self::encode_json(&status)?;
+        let updated = sqlx::query("UPDATE scans SET status_json = $1 WHERE id = $2")
+            .bind(status_json)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        if updated.rows_affected() == 0 {
+            return Err(Error::NotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn get_status(&self, id: &str) -> Result<models::Status, Error> {
+        let row = sqlx::query("SELECT status_json FROM scans WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?
+            .ok_or_else(|| Error::NotFound(id.to_string()))?;
+        Self::decode_json(
+            row.try_get::<String, _>("status_json")
+                .map_err(|e| Error::Backend(e.to_string()))?
+                .as_str(),
+        )
+    }
+
+    async fn append_results(&self, id: &str, results: Vec<models::Result>) -> Result<(), Error> {
+        for result in results {
+            let result_json = Self::encode_json(&result)?;
+            sqlx::query(
+                "INSERT INTO results (scan_id, result_id, result_json) VALUES ($1, $2, $3)",
+            )
+            .bind(id)
+            .bind(result.id as i64)
+            .bind(result_json)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    async fn get_results(
+        &self,
+        id: &str,
+        range: Option<(usize, usize)>,
+    ) -> Result<Vec<models::Result>, Error> {
+        let rows = match range {
+            Some((begin, end)) => {
+                sqlx::query(
+                    "SELECT result_json FROM results
+                     WHERE scan_id = $1 AND result_id >= $2 AND result_id <= $3
+                     ORDER BY result_id",
+                )
+                .bind(id)
+                .bind(begin as i64)
+                .bind(end as i64)
+                .fetch_all(&self.pool)
+                .await
+            }
+            None => {
+                sqlx::query(
+                    "SELECT result_json FROM results WHERE scan_id = $1 ORDER BY result_id",
+                )
+                .bind(id)
+                .fetch_all(&self.pool)
+                .await
+            }
+        }
+        .map_err(|e| Error::Backend(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Self::decode_json(
+                    row.try_get::<String, _>("result_json")
+                        .map_err(|e| Error::Backend(e.to_string()))?
+                        .as_str(),
+                )
+            })
+            .collect()
+    }
+
+    async fn set_unlock_material(&self, material: &UnlockMaterial) -> Result<(), Error> {
+        let material_json = Self::encode_json(material)?;
+        sqlx::query("DELETE FROM unlock_material")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        sqlx::query("INSERT INTO unlock_material (id, material_json) VALUES (1, $1)")
+            .bind(material_json)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn get_unlock_material(&self) -> Result<Option<UnlockMaterial>, Error> {
+        let row = sqlx::query("SELECT material_json FROM unlock_material WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::Backend(e.to_string()))?;
+        row.map(|row| {
+            Self::decode_json(
+                row.try_get::<String, _>("material_json")
+                    .map_err(|e| Error::Backend(e.to_string()))?
+                    .as_str(),
+            )
+        })
+        .transpose()
+    }
+
+    fn is_persistent(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An isolated in-memory SQLite database, migrated the same way a real
+    /// `postgres://`/`sqlite://` deployment would be.
+    async fn storage() -> SqlStorage {
+        SqlStorage::new("sqlite::memory:").await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn insert_then_get_scan_round_trips() {
+        let db = storage().await;
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        let scan = db.get_scan(&id).await.unwrap();
+        assert_eq!(scan.scan_id, id);
+    }
+
+    #[tokio::test]
+    async fn get_scan_on_unknown_id_is_not_found() {
+        let db = storage().await;
+        assert!(matches!(db.get_scan("missing").await, Err(Error::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn update_and_get_status_round_trips() {
+        let db = storage().await;
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        db.update_status(
+            &id,
+            models::Status {
+                status: models::Phase::Running,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        let status = db.get_status(&id).await.unwrap();
+        assert_eq!(status.status, models::Phase::Running);
+    }
+
+    #[tokio::test]
+    async fn update_status_on_unknown_id_is_not_found() {
+        let db = storage().await;
+        assert!(matches!(
+            db.update_status("missing", models::Status::default()).await,
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn list_scans_returns_every_inserted_id() {
+        let db = storage().await;
+        let a = db.insert_scan(models::Scan::default()).await.unwrap();
+        let b = db.insert_scan(models::Scan::default()).await.unwrap();
+        let mut ids = db.list_scans().await.unwrap();
+        ids.sort();
+        let mut expected = vec![a, b];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn append_and_get_results_respects_range_and_order() {
+        let db = storage().await;
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        db.append_results(
+            &id,
+            vec![
+                models::Result { id: 1, ..Default::default() },
+                models::Result { id: 0, ..Default::default() },
+                models::Result { id: 2, ..Default::default() },
+            ],
+        )
+        .await
+        .unwrap();
+
+        let all = db.get_results(&id, None).await.unwrap();
+        assert_eq!(all.iter().map(|r| r.id).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let ranged = db.get_results(&id, Some((1, 2))).await.unwrap();
+        assert_eq!(ranged.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn delete_scan_removes_its_results_too() {
+        let db = storage().await;
+        let id = db.insert_scan(models::Scan::default()).await.unwrap();
+        db.append_results(&id, vec![models::Result { id: 0, ..Default::default() }])
+            .await
+            .unwrap();
+
+        db.delete_scan(&id).await.unwrap();
+
+        assert!(matches!(db.get_scan(&id).await, Err(Error::NotFound(_))));
+        assert!(db.get_results(&id, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn delete_scan_on_unknown_id_is_not_found() {
+        let db = storage().await;
+        assert!(matches!(db.delete_scan("missing").await, Err(Error::NotFound(_))));
+    }
+}
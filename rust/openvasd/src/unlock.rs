@@ -0,0 +1,155 @@
+// SPDX-FileCopyrightText: 2023 Greenbone AG
+//
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! Passphrase-gated master key used to encrypt credential secrets before
+//! they are handed to a [`crate::storage::Storage`] backend.
+//!
+//! On first setup a random salt is generated and a fixed known plaintext is
+//! encrypted under the key derived from the operator's passphrase; the
+//! `{salt, verify_nonce, verify_blob}` triple is persisted. Unlocking a
+//! restarted sensor re-derives the key from the same passphrase and salt and
+//! attempts to decrypt `verify_blob` - success proves the passphrase is
+//! correct without ever storing it.
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+
+const VERIFY_PLAINTEXT: &[u8] = b"openvasd-unlock-verify-v1";
+
+/// Material persisted by the storage backend so a restarted sensor can
+/// verify an operator-supplied passphrase without storing the passphrase or
+/// the derived key.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde_support",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct UnlockMaterial {
+    salt: [u8; 16],
+    verify_nonce: [u8; 24],
+    verify_blob: Vec<u8>,
+}
+
+/// Where to obtain the operator passphrase from.
+#[derive(Debug, Clone)]
+pub enum PassphraseSource {
+    /// The passphrase is given directly, e.g. read from a secrets manager
+    /// by the caller already.
+    Literal(String),
+    /// The passphrase is read from an environment variable at unlock time.
+    EnvVar(String),
+}
+
+impl PassphraseSource {
+    fn resolve(&self) -> Result<String, Error> {
+        match self {
+            PassphraseSource::Literal(s) => Ok(s.clone()),
+            PassphraseSource::EnvVar(name) => {
+                std::env::var(name).map_err(|_| Error::MissingPassphrase(name.clone()))
+            }
+        }
+    }
+}
+
+impl From<String> for PassphraseSource {
+    fn from(value: String) -> Self {
+        PassphraseSource::Literal(value)
+    }
+}
+
+/// Error produced while setting up or unlocking the master key.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The environment variable backing a [`PassphraseSource::EnvVar`] is
+    /// not set.
+    #[error("passphrase environment variable {0} is not set")]
+    MissingPassphrase(String),
+    /// The passphrase did not decrypt the persisted verify blob.
+    #[error("invalid passphrase")]
+    InvalidPassphrase,
+    /// `unlock` was called but no [`UnlockMaterial`] has ever been persisted
+    /// via [`crate::storage::Storage::set_unlock_material`], e.g. because
+    /// the sensor has never completed its initial setup.
+    #[error("sensor has not completed unlock setup")]
+    NotSetUp,
+    /// The storage backend could not be reached while loading or persisting
+    /// the unlock material.
+    #[error("storage error: {0}")]
+    Storage(String),
+}
+
+fn derive_key(passphrase: &str, salt: &[u8; 16]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .expect("argon2id with fixed-size output does not fail");
+    key
+}
+
+/// Generates fresh unlock material for `passphrase`, returning the material
+/// to persist and the derived master key.
+pub fn setup(passphrase: &str) -> (UnlockMaterial, [u8; 32]) {
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt);
+
+    let mut verify_nonce = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut verify_nonce);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let verify_blob = cipher
+        .encrypt(XNonce::from_slice(&verify_nonce), VERIFY_PLAINTEXT)
+        .expect("encrypting the fixed verify plaintext does not fail");
+
+    (
+        UnlockMaterial {
+            salt,
+            verify_nonce,
+            verify_blob,
+        },
+        key,
+    )
+}
+
+/// Re-derives the master key from `source` and `material`, returning it only
+/// if the passphrase successfully decrypts the persisted verify blob.
+pub fn unlock(material: &UnlockMaterial, source: &PassphraseSource) -> Result<[u8; 32], Error> {
+    let passphrase = source.resolve()?;
+    let key = derive_key(&passphrase, &material.salt);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&key));
+    let nonce = XNonce::from_slice(&material.verify_nonce);
+    match cipher.decrypt(nonce, material.verify_blob.as_ref()) {
+        Ok(plaintext) if plaintext == VERIFY_PLAINTEXT => Ok(key),
+        _ => Err(Error::InvalidPassphrase),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlock_with_correct_passphrase_recovers_the_setup_key() {
+        let (material, key) = setup("correct horse battery staple");
+        let source = PassphraseSource::Literal("correct horse battery staple".to_string());
+        assert_eq!(unlock(&material, &source).unwrap(), key);
+    }
+
+    #[test]
+    fn unlock_with_wrong_passphrase_fails() {
+        let (material, _) = setup("correct horse battery staple");
+        let source = PassphraseSource::Literal("wrong".to_string());
+        assert!(matches!(unlock(&material, &source), Err(Error::InvalidPassphrase)));
+    }
+
+    #[test]
+    fn unlock_from_missing_env_var_fails() {
+        let (material, _) = setup("correct horse battery staple");
+        let source = PassphraseSource::EnvVar("OPENVASD_UNLOCK_TEST_DOES_NOT_EXIST".to_string());
+        assert!(matches!(unlock(&material, &source), Err(Error::MissingPassphrase(_))));
+    }
+}